@@ -1,22 +1,20 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    associated_token::AssociatedToken,
+    associated_token::{self, AssociatedToken},
     token::{self, Mint, Token, TokenAccount},
 };
 use mpl_token_metadata::{
-    accounts::{MasterEdition, Metadata},
+    accounts::{CollectionAuthorityRecord, MasterEdition, Metadata},
     instructions::{
-        CreateMasterEditionV3Builder, CreateMetadataAccountV3Builder, VerifyCollectionBuilder,
+        ApproveCollectionAuthorityBuilder, CreateMasterEditionV3Builder, CreateMetadataAccountV3Builder,
+        UpdateMetadataAccountV2Builder, VerifyCollectionBuilder,
     },
     types::{Collection, Creator, DataV2},
 };
-use solana_program::program::invoke;
+use solana_program::keccak;
+use solana_program::program::{invoke, invoke_signed};
 use solana_program::program_option::COption;
 use spl_token::instruction::AuthorityType;
-pub const COLLECTION_AUTHORITY: Pubkey = Pubkey::new_from_array([
-    222, 250, 88, 27, 182, 219, 236, 79, 16, 183, 187, 226, 207, 169, 201, 159, 2, 144, 44, 84, 55,
-    145, 166, 31, 193, 157, 36, 6, 64, 119, 54, 33,
-]);
 
 declare_id!("CfHwW1HDUDn8eRPqHEV7c8n98JFo2bsHDAuYLi2onWsv");
 
@@ -24,12 +22,490 @@ declare_id!("CfHwW1HDUDn8eRPqHEV7c8n98JFo2bsHDAuYLi2onWsv");
 pub mod nft_program {
     use super::*;
 
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        treasury: Pubkey,
+        royalty_bps: u16,
+        platform_fee_bps: u16,
+        timelock_delay: i64,
+    ) -> Result<()> {
+        MarketplaceConfig::validate_fees(royalty_bps, platform_fee_bps)?;
+        require!(
+            (0..=MarketplaceConfig::MAX_TIMELOCK_DELAY).contains(&timelock_delay),
+            CustomError::InvalidTimelockDelay
+        );
+
+        // Fund the treasury PDA up to rent exemption so small fees can land in it
+        let treasury_rent = Rent::get()?.minimum_balance(0);
+        let treasury_lamports = ctx.accounts.treasury.lamports();
+        if treasury_lamports < treasury_rent {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: ctx.accounts.treasury.to_account_info(),
+                    },
+                ),
+                treasury_rent - treasury_lamports,
+            )?;
+        }
+
+        let config = &mut ctx.accounts.config;
+        config.authority = ctx.accounts.authority.key();
+        config.treasury = treasury;
+        config.royalty_bps = royalty_bps;
+        config.platform_fee_bps = platform_fee_bps;
+        config.paused = false;
+        config.pending_authority = None;
+        config.timelock_delay = timelock_delay;
+        config.next_change_id = 0;
+        config.multisig = false;
+        config.payment_mints = Vec::new();
+        config.prune_bounty = 0;
+        config.bump = ctx.bumps.config;
+        config.treasury_bump = ctx.bumps.treasury;
+
+        msg!("Marketplace config initialized, authority: {}", config.authority);
+
+        Ok(())
+    }
+
+    // Config changes are queued and only land after the timelock delay, so
+    // players can see royalty, fee and authority changes coming.
+    pub fn queue_config_change(
+        ctx: Context<QueueConfigChange>,
+        change: ConfigChange,
+    ) -> Result<()> {
+        authorize_admin_action(
+            &ctx.accounts.config,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.admin_set.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+            ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+            AdminAction::ConfigChange(change.clone()),
+        )?;
+
+        let config = &mut ctx.accounts.config;
+        change.validate(config)?;
+
+        let clock = Clock::get()?;
+        let effective_at = PendingChange::effective_at(clock.unix_timestamp, config.timelock_delay)?;
+
+        let pending_change = &mut ctx.accounts.pending_change;
+        pending_change.id = config.next_change_id;
+        pending_change.payer = ctx.accounts.authority.key();
+        pending_change.change = change;
+        pending_change.effective_at = effective_at;
+        pending_change.bump = ctx.bumps.pending_change;
+
+        config.next_change_id = config
+            .next_change_id
+            .checked_add(1)
+            .ok_or(CustomError::Overflow)?;
+
+        msg!(
+            "Queued config change {} effective at {}",
+            pending_change.id,
+            effective_at
+        );
+
+        Ok(())
+    }
+
+    pub fn execute_pending_change(ctx: Context<ExecutePendingChange>) -> Result<()> {
+        let clock = Clock::get()?;
+        let pending_change = &ctx.accounts.pending_change;
+        require!(
+            pending_change.is_ready(clock.unix_timestamp),
+            CustomError::TimelockNotElapsed
+        );
+
+        let config = &mut ctx.accounts.config;
+        pending_change.change.validate(config)?;
+        config.apply(&pending_change.change);
+
+        msg!("Executed config change {}", pending_change.id);
+
+        Ok(())
+    }
+
+    pub fn cancel_pending_change(ctx: Context<CancelPendingChange>) -> Result<()> {
+        authorize_admin_action(
+            &ctx.accounts.config,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.admin_set.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+            ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+            AdminAction::CancelPendingChange {
+                id: ctx.accounts.pending_change.id,
+            },
+        )?;
+
+        msg!("Cancelled config change {}", ctx.accounts.pending_change.id);
+
+        Ok(())
+    }
+
+    pub fn create_admin_set(
+        ctx: Context<CreateAdminSet>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        AdminSet::validate(&signers, threshold)?;
+
+        let admin_set = &mut ctx.accounts.admin_set;
+        admin_set.signers = signers;
+        admin_set.threshold = threshold;
+        admin_set.next_proposal_id = 0;
+        admin_set.bump = ctx.bumps.admin_set;
+
+        ctx.accounts.config.multisig = true;
+
+        msg!(
+            "Admin set created: {} of {}",
+            threshold,
+            admin_set.signers.len()
+        );
+
+        Ok(())
+    }
+
+    pub fn update_admin_set(
+        ctx: Context<UpdateAdminSet>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        AdminSet::validate(&signers, threshold)?;
+
+        authorize_admin_action(
+            &ctx.accounts.config,
+            &ctx.accounts.authority.key(),
+            Some(&ctx.accounts.admin_set),
+            Some(&mut ctx.accounts.proposal),
+            Some(ctx.accounts.proposer.to_account_info()),
+            AdminAction::UpdateAdminSet {
+                signers: signers.clone(),
+                threshold,
+            },
+        )?;
+
+        let admin_set = &mut ctx.accounts.admin_set;
+        admin_set.signers = signers;
+        admin_set.threshold = threshold;
+
+        msg!(
+            "Admin set updated: {} of {}",
+            threshold,
+            admin_set.signers.len()
+        );
+
+        Ok(())
+    }
+
+    pub fn create_proposal(ctx: Context<CreateProposal>, action: AdminAction) -> Result<()> {
+        let proposer = ctx.accounts.proposer.key();
+        let admin_set = &mut ctx.accounts.admin_set;
+        require!(
+            admin_set.signers.contains(&proposer),
+            CustomError::NotAdminSigner
+        );
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.id = admin_set.next_proposal_id;
+        proposal.proposer = proposer;
+        proposal.action = action;
+        proposal.approvals = vec![proposer];
+        proposal.executed = false;
+        proposal.bump = ctx.bumps.proposal;
+
+        admin_set.next_proposal_id = admin_set
+            .next_proposal_id
+            .checked_add(1)
+            .ok_or(CustomError::Overflow)?;
+
+        msg!("Created proposal {}", proposal.id);
+
+        Ok(())
+    }
+
+    pub fn approve_proposal(ctx: Context<ApproveProposal>) -> Result<()> {
+        let approver = ctx.accounts.approver.key();
+        require!(
+            ctx.accounts.admin_set.signers.contains(&approver),
+            CustomError::NotAdminSigner
+        );
+
+        let proposal = &mut ctx.accounts.proposal;
+        require!(!proposal.executed, CustomError::ProposalAlreadyExecuted);
+        require!(
+            !proposal.approvals.contains(&approver),
+            CustomError::AlreadyApproved
+        );
+        proposal.approvals.push(approver);
+
+        msg!(
+            "Proposal {} approvals: {}",
+            proposal.id,
+            proposal.approvals.len()
+        );
+
+        Ok(())
+    }
+
+    // The proposed authority signs, so the handover can't land on a key nobody
+    // controls, alongside the current authority or, once an admin set is
+    // configured, an approved proposal. Every collection registry passed in
+    // `remaining_accounts` is handed to the new authority. Metadata accounts
+    // passed there have their update authority moved too, which Metaplex only
+    // allows with the current authority's signature.
+    pub fn accept_authority<'info>(
+        ctx: Context<'_, '_, '_, 'info, AcceptAuthority<'info>>,
+    ) -> Result<()> {
+        let old_authority = ctx.accounts.config.authority;
+        let new_authority = ctx.accounts.new_authority.key();
+        require!(
+            ctx.accounts.config.pending_authority == Some(new_authority),
+            CustomError::InvalidNewAuthority
+        );
+
+        let signer = ctx.accounts.authority.as_ref().map(|authority| authority.key());
+        authorize_admin_action(
+            &ctx.accounts.config,
+            &signer.unwrap_or_default(),
+            ctx.accounts.admin_set.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+            ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+            AdminAction::AcceptAuthority { new_authority },
+        )?;
+
+        for account in ctx.remaining_accounts.iter() {
+            if *account.owner == crate::ID {
+                let mut registry = CollectionRegistry::try_deserialize(
+                    &mut &account.data.borrow()[..],
+                )?;
+                require_keys_eq!(
+                    account.key(),
+                    Pubkey::create_program_address(
+                        &[
+                            b"collection",
+                            registry.collection_mint.as_ref(),
+                            &[registry.bump],
+                        ],
+                        &crate::ID,
+                    )
+                    .map_err(|_| CustomError::InvalidCollectionAuthority)?,
+                    CustomError::InvalidCollectionAuthority
+                );
+                require_keys_eq!(
+                    registry.authority,
+                    old_authority,
+                    CustomError::InvalidCollectionAuthority
+                );
+
+                registry.authority = new_authority;
+                registry.try_serialize(&mut &mut account.data.borrow_mut()[..])?;
+
+                msg!("Moved collection authority on {}", registry.collection_mint);
+                continue;
+            }
+
+            require_keys_eq!(
+                *account.owner,
+                mpl_token_metadata::ID,
+                CustomError::IncorrectOwner
+            );
+            let metadata = Metadata::try_from(account)
+                .map_err(|_| CustomError::InvalidMetadata)?;
+            require_keys_eq!(
+                metadata.update_authority,
+                old_authority,
+                CustomError::InvalidCollectionAuthority
+            );
+            let authority = ctx
+                .accounts
+                .authority
+                .as_ref()
+                .filter(|authority| authority.key() == old_authority)
+                .ok_or(CustomError::InvalidConfigAuthority)?;
+
+            let update_ix = UpdateMetadataAccountV2Builder::new()
+                .metadata(account.key())
+                .update_authority(old_authority)
+                .new_update_authority(new_authority)
+                .instruction();
+
+            invoke(
+                &update_ix,
+                &[
+                    account.clone(),
+                    authority.to_account_info(),
+                    ctx.accounts.metadata_program.to_account_info(),
+                ],
+            )?;
+
+            msg!("Moved update authority on {}", metadata.mint);
+        }
+
+        let config = &mut ctx.accounts.config;
+        config.authority = new_authority;
+        config.pending_authority = None;
+
+        msg!("Authority transferred from {} to {}", old_authority, new_authority);
+
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, scope: PauseScope, paused: bool) -> Result<()> {
+        authorize_admin_action(
+            &ctx.accounts.config,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.admin_set.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+            ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+            AdminAction::SetPaused { scope, paused },
+        )?;
+
+        let config = &mut ctx.accounts.config;
+        match scope {
+            PauseScope::All => config.paused = paused,
+            PauseScope::Mint => config.mint_paused = paused,
+            PauseScope::List => config.list_paused = paused,
+            PauseScope::Purchase => config.purchase_paused = paused,
+        }
+
+        msg!("Pause state updated: {:?} = {}", scope, paused);
+
+        Ok(())
+    }
+
+    pub fn withdraw_treasury(ctx: Context<WithdrawTreasury>, amount: u64) -> Result<()> {
+        authorize_admin_action(
+            &ctx.accounts.config,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.admin_set.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+            ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+            AdminAction::WithdrawTreasury { amount },
+        )?;
+
+        let treasury_rent = Rent::get()?.minimum_balance(0);
+        let available = ctx
+            .accounts
+            .treasury
+            .lamports()
+            .saturating_sub(treasury_rent);
+        require!(amount <= available, CustomError::InsufficientFunds);
+
+        let seeds = &[b"treasury".as_ref(), &[ctx.accounts.config.treasury_bump]];
+        let signer = &[&seeds[..]];
+
+        anchor_lang::system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.treasury.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                },
+                signer,
+            ),
+            amount,
+        )?;
+
+        msg!("Withdrew {} lamports from treasury", amount);
+
+        Ok(())
+    }
+
+    pub fn withdraw_treasury_tokens(ctx: Context<WithdrawTreasuryTokens>, amount: u64) -> Result<()> {
+        let payment_mint = ctx.accounts.payment_mint.key();
+        authorize_admin_action(
+            &ctx.accounts.config,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.admin_set.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+            ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+            AdminAction::WithdrawTreasuryTokens {
+                mint: payment_mint,
+                amount,
+            },
+        )?;
+
+        require!(
+            amount <= ctx.accounts.treasury_token_account.amount,
+            CustomError::InsufficientFunds
+        );
+
+        let seeds = &[b"treasury".as_ref(), &[ctx.accounts.config.treasury_bump]];
+        let signer = &[&seeds[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.treasury_token_account.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.treasury.to_account_info(),
+                },
+                signer,
+            ),
+            amount,
+        )?;
+
+        msg!("Withdrew {} of {} from treasury", amount, payment_mint);
+
+        Ok(())
+    }
+
+    pub fn set_payment_mint(ctx: Context<SetPaymentMint>, mint: Pubkey, accepted: bool) -> Result<()> {
+        authorize_admin_action(
+            &ctx.accounts.config,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.admin_set.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+            ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+            AdminAction::SetPaymentMint { mint, accepted },
+        )?;
+
+        let config = &mut ctx.accounts.config;
+        let position = config.payment_mints.iter().position(|m| *m == mint);
+        match (accepted, position) {
+            (true, None) => {
+                require!(
+                    config.payment_mints.len() < MarketplaceConfig::MAX_PAYMENT_MINTS,
+                    CustomError::TooManyPaymentMints
+                );
+                config.payment_mints.push(mint);
+            }
+            (false, Some(index)) => {
+                config.payment_mints.remove(index);
+            }
+            _ => {}
+        }
+
+        msg!("Payment mint {} accepted: {}", mint, accepted);
+
+        Ok(())
+    }
+
     pub fn create_collection(
         ctx: Context<CreateCollection>,
         name: String,
         symbol: String,
         uri: String,
     ) -> Result<()> {
+        authorize_admin_action(
+            &ctx.accounts.config,
+            &ctx.accounts.payer.key(),
+            ctx.accounts.admin_set.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+            ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+            AdminAction::CreateCollection {
+                collection_mint: ctx.accounts.collection_mint.key(),
+                collection_authority: ctx.accounts.collection_authority.key(),
+            },
+        )?;
+
         // Mint collection NFT
         token::mint_to(
             CpiContext::new(
@@ -37,7 +513,7 @@ pub mod nft_program {
                 token::MintTo {
                     mint: ctx.accounts.collection_mint.to_account_info(),
                     to: ctx.accounts.collection_token_account.to_account_info(),
-                    authority: ctx.accounts.payer.to_account_info(),
+                    authority: ctx.accounts.collection_authority.to_account_info(),
                 },
             ),
             1,
@@ -47,7 +523,7 @@ pub mod nft_program {
         let metadata_ix = CreateMetadataAccountV3Builder::new()
             .metadata(ctx.accounts.collection_metadata.key())
             .mint(ctx.accounts.collection_mint.key())
-            .mint_authority(ctx.accounts.payer.key())
+            .mint_authority(ctx.accounts.collection_authority.key())
             .payer(ctx.accounts.payer.key())
             .update_authority(ctx.accounts.collection_authority.key(), true)
             .data(DataV2 {
@@ -80,7 +556,7 @@ pub mod nft_program {
             .edition(ctx.accounts.collection_master_edition.key())
             .mint(ctx.accounts.collection_mint.key())
             .update_authority(ctx.accounts.collection_authority.key())
-            .mint_authority(ctx.accounts.payer.key())
+            .mint_authority(ctx.accounts.collection_authority.key())
             .metadata(ctx.accounts.collection_metadata.key())
             .payer(ctx.accounts.payer.key())
             .max_supply(0)
@@ -101,6 +577,48 @@ pub mod nft_program {
             ],
         )?;
 
+        // Delegate collection verification to the registry PDA so public mints
+        // can be verified without the collection authority signing
+        let approve_ix = ApproveCollectionAuthorityBuilder::new()
+            .collection_authority_record(ctx.accounts.collection_authority_record.key())
+            .new_collection_authority(ctx.accounts.collection_registry.key())
+            .update_authority(ctx.accounts.collection_authority.key())
+            .payer(ctx.accounts.payer.key())
+            .metadata(ctx.accounts.collection_metadata.key())
+            .mint(ctx.accounts.collection_mint.key())
+            .rent(Some(ctx.accounts.rent.key()))
+            .instruction();
+
+        invoke(
+            &approve_ix,
+            &[
+                ctx.accounts.collection_authority_record.to_account_info(),
+                ctx.accounts.collection_registry.to_account_info(),
+                ctx.accounts.collection_authority.to_account_info(),
+                ctx.accounts.payer.to_account_info(),
+                ctx.accounts.collection_metadata.to_account_info(),
+                ctx.accounts.collection_mint.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.rent.to_account_info(),
+            ],
+        )?;
+
+        let registry = &mut ctx.accounts.collection_registry;
+        registry.collection_mint = ctx.accounts.collection_mint.key();
+        registry.authority = ctx.accounts.collection_authority.key();
+        registry.royalty_bps = ctx.accounts.config.royalty_bps;
+        registry.minted = 0;
+        registry.mint_price = 0;
+        registry.allowlist_root = None;
+        registry.phases = Vec::new();
+        registry.phases_version = 0;
+        registry.max_per_wallet = 0;
+        registry.max_supply = 0;
+        registry.rarity_caps = [0; RarityType::COUNT];
+        registry.rarity_minted = [0; RarityType::COUNT];
+        registry.status = CollectionStatus::Active;
+        registry.bump = ctx.bumps.collection_registry;
+
         msg!("Finished Generating Collection!");
         msg!("Collection Master Edition: {}", ctx.accounts.collection_master_edition.key());
 msg!("Collection Metadata: {}", ctx.accounts.collection_metadata.key());
@@ -122,194 +640,303 @@ msg!("Rent Sysvar: {}", ctx.accounts.rent.key());
         uri: String,
         stats: CardStats,
     ) -> Result<()> {
-        // Input validation
-        require!(name.len() <= 32, CustomError::NameTooLong);
-        require!(symbol.len() <= 10, CustomError::SymbolTooLong);
-        require!(uri.len() <= 200, CustomError::UriTooLong);
-        require!(stats.attack <= 100, CustomError::InvalidStats);
-        require!(stats.defense <= 100, CustomError::InvalidStats);
-
-        msg!("Debug - Mint account: {}", ctx.accounts.mint.key());
-        match ctx.accounts.payer.to_account_info().key {
-            key => msg!("Debug - Payer: {}", key),
-        }
-        
-        match ctx.accounts.stats_account.to_account_info().key {
-            key => msg!("Debug - Stats account: {}", key),
-        }       
-   
-
-        // Mint token
-        token::mint_to(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                token::MintTo {
-                    mint: ctx.accounts.mint.to_account_info(),
-                    to: ctx.accounts.token_account.to_account_info(),
-                    authority: ctx.accounts.payer.to_account_info(),
-                },
-            ),
-            1,
-        )?;
+        // Without an authority co-signature the collection must be on sale
+        let public_mint = match ctx.accounts.collection_authority {
+            Some(_) => None,
+            None => {
+                let now = Clock::get()?.unix_timestamp;
+                let terms = ctx
+                    .accounts
+                    .collection_registry
+                    .public_mint_terms(MintEligibility::Public, now)?;
+                Some(terms)
+            }
+        };
 
-        msg!("Finished Minting");
+        process_mint(ctx.accounts, &ctx.bumps, name, symbol, uri, stats, public_mint)
+    }
 
-        // Create metadata
-        let creators = vec![Creator {
-            address: ctx.accounts.collection_authority.key(),
-            verified: true,
-            share: 100,
-        }];
+    pub fn mint_nft_allowlisted(
+        ctx: Context<MintNFTAllowlisted>,
+        name: String,
+        symbol: String,
+        uri: String,
+        stats: CardStats,
+        allowance: u32,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let payer = ctx.accounts.mint_nft.payer.key();
+        let registry = &ctx.accounts.mint_nft.collection_registry;
+        let root = registry.allowlist_root.ok_or(CustomError::AllowlistNotEnabled)?;
 
-        let metadata_ix = CreateMetadataAccountV3Builder::new()
-            .metadata(ctx.accounts.metadata.key())
-            .mint(ctx.accounts.mint.key())
-            .mint_authority(ctx.accounts.payer.key())
-            .payer(ctx.accounts.payer.key())
-            .update_authority(ctx.accounts.collection_authority.key(), true)
-            .data(DataV2 {
-                name,
-                symbol,
-                uri,
-                seller_fee_basis_points: 300,
-                creators: Some(creators),
-                collection: Some(Collection {
-                    key: ctx.accounts.collection_mint.key(),
-                    verified: false, // Will be verified in the next step
-                }),
-                uses: None,
-            }).is_mutable(false)
-            .instruction();
+        let leaf = allowlist_leaf(&payer, allowance);
+        require!(
+            verify_merkle_proof(&proof, root, leaf),
+            CustomError::InvalidAllowlistProof
+        );
 
-        invoke(
-            &metadata_ix,
-            &[
-                ctx.accounts.metadata.to_account_info(),
-                ctx.accounts.mint.to_account_info(),
-                ctx.accounts.payer.to_account_info(),
-                ctx.accounts.collection_authority.to_account_info(),
-                ctx.accounts.metadata_program.to_account_info(),
-                ctx.accounts.token_program.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-                ctx.accounts.rent.to_account_info(),
-            ],
-        )?;
+        let claim = &mut ctx.accounts.allowlist_claim;
+        claim.minted = claim.minted.checked_add(1).ok_or(CustomError::Overflow)?;
+        require!(
+            allowance == 0 || claim.minted <= allowance,
+            CustomError::AllowlistAllowanceExceeded
+        );
+        claim.bump = ctx.bumps.allowlist_claim;
 
-        msg!("Finished Metadata assignment");
+        let now = Clock::get()?.unix_timestamp;
+        let terms = registry.public_mint_terms(MintEligibility::Allowlist, now)?;
+        process_mint(
+            &mut ctx.accounts.mint_nft,
+            &ctx.bumps.mint_nft,
+            name,
+            symbol,
+            uri,
+            stats,
+            Some(terms),
+        )
+    }
 
-        let master_edition_ix = CreateMasterEditionV3Builder::new()
-            .edition(ctx.accounts.master_edition.key())
-            .mint(ctx.accounts.mint.key())
-            .update_authority(ctx.accounts.collection_authority.key())
-            .mint_authority(ctx.accounts.payer.key())
-            .metadata(ctx.accounts.metadata.key())
-            .payer(ctx.accounts.payer.key())
-            .max_supply(0)
-            .instruction();
+    pub fn set_collection_status(
+        ctx: Context<SetCollectionStatus>,
+        status: CollectionStatus,
+    ) -> Result<()> {
+        // Without an admin set the marketplace authority can also act here
+        let signer = ctx.accounts.authority.key();
+        if ctx.accounts.config.multisig || signer != ctx.accounts.config.authority {
+            authorize_collection_action(
+                &ctx.accounts.config,
+                &ctx.accounts.collection_registry,
+                &signer,
+                ctx.accounts.admin_set.as_ref(),
+                ctx.accounts.proposal.as_mut(),
+                ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+                AdminAction::SetCollectionStatus {
+                    collection_mint: ctx.accounts.collection_registry.collection_mint,
+                    status,
+                },
+            )?;
+        }
 
-        invoke(
-            &master_edition_ix,
-            &[
-                ctx.accounts.master_edition.to_account_info(),
-                ctx.accounts.metadata.to_account_info(),
-                ctx.accounts.mint.to_account_info(),
-                ctx.accounts.payer.to_account_info(),
-                ctx.accounts.collection_authority.to_account_info(),
-                ctx.accounts.metadata_program.to_account_info(),
-                ctx.accounts.token_program.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-                ctx.accounts.rent.to_account_info(),
-            ],
-        )?;
-        msg!("Finished Master Eddition assignment");
+        let registry = &mut ctx.accounts.collection_registry;
+        registry.status = status;
 
-        msg!("Starting verification process");
-        msg!("Token Metadata Program ID: {}", mpl_token_metadata::ID);
-        msg!(
-            "Metadata account: {} owned by {}",
-            ctx.accounts.metadata.key(),
-            ctx.accounts.metadata.owner
-        );
-        msg!(
-            "Collection metadata: {} owned by {}",
-            ctx.accounts.collection_metadata.key(),
-            ctx.accounts.collection_metadata.owner
-        );
         msg!(
-            "Collection master edition: {} owned by {}",
-            ctx.accounts.collection_master_edition.key(),
-            ctx.accounts.collection_master_edition.owner
-        );
-        msg!(
-            "Collection authority: {}",
-            ctx.accounts.collection_authority.key()
+            "Collection {} status: {:?}",
+            registry.collection_mint,
+            registry.status
         );
 
-        // Verify the collection
-        let verify_ix = VerifyCollectionBuilder::new()
-            .metadata(ctx.accounts.metadata.key())
-            .collection_authority(ctx.accounts.collection_authority.key())
-            .payer(ctx.accounts.payer.key())
-            .collection_mint(ctx.accounts.collection_mint.key())
-            .collection(ctx.accounts.collection_metadata.key())
-            .collection_master_edition_account(ctx.accounts.collection_master_edition.key())
-            .instruction();
+        Ok(())
+    }
 
-        invoke(
-            &verify_ix,
-            &[
-                ctx.accounts.metadata.to_account_info(),
-                ctx.accounts.collection_authority.to_account_info(),
-                ctx.accounts.payer.to_account_info(),
-                ctx.accounts.collection_mint.to_account_info(),
-                ctx.accounts.collection_metadata.to_account_info(),
-                ctx.accounts.collection_master_edition.to_account_info(),
-            ],
+    pub fn set_mint_price(ctx: Context<UpdateCollection>, mint_price: u64) -> Result<()> {
+        let registry = &mut ctx.accounts.collection_registry;
+        registry.mint_price = mint_price;
+
+        msg!("Collection {} mint price: {}", registry.collection_mint, mint_price);
+
+        Ok(())
+    }
+
+    pub fn set_allowlist_root(
+        ctx: Context<UpdateCollection>,
+        allowlist_root: Option<[u8; 32]>,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.collection_registry;
+        registry.allowlist_root = allowlist_root;
+
+        msg!("Collection {} allowlist updated", registry.collection_mint);
+
+        Ok(())
+    }
+
+    // Caps can only be introduced or tightened, never raised or removed, so
+    // scarcity promised to players holds for the life of the collection.
+    pub fn set_supply_caps(
+        ctx: Context<UpdateCollection>,
+        max_supply: u64,
+        rarity_caps: [u32; RarityType::COUNT],
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.collection_registry;
+
+        require!(
+            is_tighter_cap(registry.max_supply, max_supply, registry.minted),
+            CustomError::InvalidSupplyCap
+        );
+        for (i, cap) in rarity_caps.iter().enumerate() {
+            require!(
+                is_tighter_cap(
+                    registry.rarity_caps[i] as u64,
+                    *cap as u64,
+                    registry.rarity_minted[i] as u64
+                ),
+                CustomError::InvalidSupplyCap
+            );
+        }
+
+        registry.max_supply = max_supply;
+        registry.rarity_caps = rarity_caps;
+
+        msg!(
+            "Collection {} max supply: {}",
+            registry.collection_mint,
+            max_supply
+        );
+
+        Ok(())
+    }
+
+    pub fn set_mint_limit(ctx: Context<UpdateCollection>, max_per_wallet: u32) -> Result<()> {
+        let registry = &mut ctx.accounts.collection_registry;
+        registry.max_per_wallet = max_per_wallet;
+
+        msg!(
+            "Collection {} max per wallet: {}",
+            registry.collection_mint,
+            max_per_wallet
+        );
+
+        Ok(())
+    }
+
+    pub fn set_mint_phases(ctx: Context<UpdateCollection>, phases: Vec<MintPhase>) -> Result<()> {
+        require!(
+            phases.len() <= CollectionRegistry::MAX_PHASES,
+            CustomError::InvalidMintPhases
+        );
+        for (i, phase) in phases.iter().enumerate() {
+            require!(phase.start_at < phase.end_at, CustomError::InvalidMintPhases);
+            if i > 0 {
+                require!(
+                    phases[i - 1].end_at <= phase.start_at,
+                    CustomError::InvalidMintPhases
+                );
+            }
+        }
+
+        let registry = &mut ctx.accounts.collection_registry;
+        registry.phases = phases;
+        registry.phases_version = registry
+            .phases_version
+            .checked_add(1)
+            .ok_or(CustomError::Overflow)?;
+
+        msg!(
+            "Collection {} mint phases: {}",
+            registry.collection_mint,
+            registry.phases.len()
+        );
+
+        Ok(())
+    }
+
+    pub fn withdraw_collection_funds(
+        ctx: Context<WithdrawCollectionFunds>,
+        amount: u64,
+    ) -> Result<()> {
+        authorize_collection_action(
+            &ctx.accounts.config,
+            &ctx.accounts.collection_registry,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.admin_set.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+            ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+            AdminAction::WithdrawCollectionFunds {
+                collection_mint: ctx.accounts.collection_registry.collection_mint,
+                amount,
+            },
         )?;
 
-        msg!("Finished Verification");
+        let registry_info = ctx.accounts.collection_registry.to_account_info();
+        let rent_floor = Rent::get()?.minimum_balance(registry_info.data_len());
+        let available = registry_info.lamports().saturating_sub(rent_floor);
+        require!(amount <= available, CustomError::InsufficientFunds);
 
-        let stats_account = &mut ctx.accounts.stats_account;
-        stats_account.mint = ctx.accounts.mint.key();
-        stats_account.attack = stats.attack;
-        stats_account.defense = stats.defense;
-        stats_account.element = stats.element;
-        stats_account.rarity = stats.rarity;
+        ctx.accounts.collection_registry.sub_lamports(amount)?;
+        ctx.accounts.destination.add_lamports(amount)?;
 
-        msg!("Finished Stats Account assignment");
+        msg!("Withdrew {} lamports from collection treasury", amount);
 
-        // Revoke mint authority (set to None)
-        token::set_authority(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                token::SetAuthority {
-                    current_authority: ctx.accounts.payer.to_account_info(),
-                    account_or_mint: ctx.accounts.mint.to_account_info(),
-                },
-            ),
-            AuthorityType::MintTokens, // Change this from token::AuthorityType to just AuthorityType
-            None,
+        Ok(())
+    }
+
+    /// Withdraws SPL royalties paid to the collection registry, which is the
+    /// verified creator of publicly minted cards.
+    pub fn withdraw_collection_tokens(
+        ctx: Context<WithdrawCollectionTokens>,
+        amount: u64,
+    ) -> Result<()> {
+        authorize_collection_action(
+            &ctx.accounts.config,
+            &ctx.accounts.collection_registry,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.admin_set.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+            ctx.accounts.proposer.as_ref().map(|proposer| proposer.to_account_info()),
+            AdminAction::WithdrawCollectionTokens {
+                collection_mint: ctx.accounts.collection_registry.collection_mint,
+                mint: ctx.accounts.payment_mint.key(),
+                amount,
+            },
         )?;
 
-        token::set_authority(
-            CpiContext::new(
+        require!(
+            amount <= ctx.accounts.registry_token_account.amount,
+            CustomError::InsufficientFunds
+        );
+
+        let registry = &ctx.accounts.collection_registry;
+        let seeds = &[
+            b"collection".as_ref(),
+            registry.collection_mint.as_ref(),
+            &[registry.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                token::SetAuthority {
-                    current_authority: ctx.accounts.payer.to_account_info(),
-                    account_or_mint: ctx.accounts.mint.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.registry_token_account.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.collection_registry.to_account_info(),
                 },
+                signer,
             ),
-            AuthorityType::FreezeAccount,
-            None,
+            amount,
         )?;
 
-        msg!("Finished Authority assignment");
+        msg!(
+            "Withdrew {} of {} from collection treasury",
+            amount,
+            ctx.accounts.payment_mint.key()
+        );
 
         Ok(())
     }
 
-    pub fn list_nft(ctx: Context<ListNFT>, price: u64) -> Result<()> {
-        require!(price > 0, CustomError::InvalidPrice);
+    pub fn list_nft(
+        ctx: Context<ListNFT>,
+        price: u64,
+        payment_mint: Option<Pubkey>,
+        dutch: Option<DutchPricing>,
+        expires_at: Option<i64>,
+        reserved_buyer: Option<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::List),
+            CustomError::ProgramPaused
+        );
+
+        let clock = Clock::get()?;
+        let terms = ListingTerms {
+            price,
+            payment_mint,
+            dutch,
+            expires_at,
+            reserved_buyer,
+        };
+        terms.validate(&ctx.accounts.config, clock.unix_timestamp)?;
         require!(
             ctx.accounts.token_account.amount == 1,
             CustomError::InvalidNFTOwnership
@@ -318,53 +945,112 @@ msg!("Rent Sysvar: {}", ctx.accounts.rent.key());
             ctx.accounts.token_account.owner == ctx.accounts.seller.key(),
             CustomError::NotNFTOwner
         );
-
-        let clock = Clock::get()?;
-        let listing = &mut ctx.accounts.listing;
-
-        require!(
-            listing.history.is_empty() || listing.status == ListingStatus::NotActive,
-            CustomError::ListingAlreadyActive
-        );
-
-        // Store listing key before using it
+        ctx.accounts.listing.ensure_reusable()?;
 
         token::approve(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 token::Approve {
                     to: ctx.accounts.token_account.to_account_info(),
-                    delegate: listing.to_account_info(),
+                    delegate: ctx.accounts.listing.to_account_info(),
                     authority: ctx.accounts.seller.to_account_info(),
                 },
             ),
             1,
         )?;
 
+        // Delegated listings can go stale, so the seller posts the prune
+        // bounty up front and gets it back unless someone has to prune
+        let deposit = ctx.accounts.config.prune_bounty;
+        if deposit > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.seller.to_account_info(),
+                        to: ctx.accounts.listing.to_account_info(),
+                    },
+                ),
+                deposit,
+            )?;
+        }
+
         // Update listing after CPI
-        listing.status = ListingStatus::Active;
-        listing.seller = ctx.accounts.seller.key();
-        listing.mint = ctx.accounts.mint.key();
-        listing.price = price;
-        listing.created_at = clock.unix_timestamp;
+        ctx.accounts.listing.open(
+            ctx.accounts.seller.key(),
+            ctx.accounts.mint.key(),
+            terms,
+            false,
+            clock.unix_timestamp,
+        );
+        ctx.accounts.listing.bounty_deposit = deposit;
+
+        Ok(())
+    }
+
+    /// Lists a card by moving it into a vault owned by the listing PDA
+    /// instead of delegating it, so the listing can't go stale while open.
+    /// The card is returned to the seller on cancel.
+    pub fn list_nft_in_vault(
+        ctx: Context<ListNFTInVault>,
+        price: u64,
+        payment_mint: Option<Pubkey>,
+        dutch: Option<DutchPricing>,
+        expires_at: Option<i64>,
+        reserved_buyer: Option<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::List),
+            CustomError::ProgramPaused
+        );
 
-        listing.history.push(TradeHistory {
+        let clock = Clock::get()?;
+        let terms = ListingTerms {
             price,
-            timestamp: clock.unix_timestamp,
-            action: TradeAction::List,
-        });
+            payment_mint,
+            dutch,
+            expires_at,
+            reserved_buyer,
+        };
+        terms.validate(&ctx.accounts.config, clock.unix_timestamp)?;
+        ctx.accounts.listing.ensure_reusable()?;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.token_account.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.seller.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        ctx.accounts.listing.open(
+            ctx.accounts.seller.key(),
+            ctx.accounts.mint.key(),
+            terms,
+            true,
+            clock.unix_timestamp,
+        );
 
         Ok(())
     }
 
-    pub fn update_listing(ctx: Context<UpdateListing>, new_price: u64) -> Result<()> {
-        require!(new_price > 0, CustomError::InvalidPrice);
+    pub fn update_listing(
+        ctx: Context<UpdateListing>,
+        new_price: u64,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
         require!(
-            ctx.accounts.token_account.owner == ctx.accounts.seller.key(),
-            CustomError::NotNFTOwner
+            !ctx.accounts.config.is_paused(PauseScope::List),
+            CustomError::ProgramPaused
         );
+        require!(new_price > 0, CustomError::InvalidPrice);
 
         let clock = Clock::get()?;
+        ListingData::validate_expiry(expires_at, clock.unix_timestamp)?;
 
         // Get the listing info BEFORE creating the mutable reference
         let listing_info = ctx.accounts.listing.to_account_info();
@@ -374,110 +1060,217 @@ msg!("Rent Sysvar: {}", ctx.accounts.rent.key());
             matches!(listing.status, ListingStatus::Active),
             CustomError::ListingNotActive
         );
+        // Dutch listings reprice themselves; relist to change the schedule
+        require!(listing.dutch.is_none(), CustomError::DutchListingNotUpdatable);
 
-        // Revoke existing delegate approval
-        token::revoke(CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            token::Revoke {
-                source: ctx.accounts.token_account.to_account_info(),
-                authority: ctx.accounts.seller.to_account_info(),
-            },
-        ))?;
+        // Vaulted cards are already held by the listing
+        if !listing.vaulted {
+            let token_account = ctx
+                .accounts
+                .token_account
+                .as_ref()
+                .ok_or(CustomError::InvalidNFTOwnership)?;
+            require!(
+                token_account.owner == ctx.accounts.seller.key(),
+                CustomError::NotNFTOwner
+            );
 
-        // Create new delegate approval with updated listing
-        token::approve(
-            CpiContext::new(
+            // Revoke existing delegate approval
+            token::revoke(CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                token::Approve {
-                    to: ctx.accounts.token_account.to_account_info(),
-                    delegate: listing_info, // Use stored listing_info instead
+                token::Revoke {
+                    source: token_account.to_account_info(),
                     authority: ctx.accounts.seller.to_account_info(),
                 },
-            ),
-            1,
-        )?;
+            ))?;
+
+            // Create new delegate approval with updated listing
+            token::approve(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Approve {
+                        to: token_account.to_account_info(),
+                        delegate: listing_info, // Use stored listing_info instead
+                        authority: ctx.accounts.seller.to_account_info(),
+                    },
+                ),
+                1,
+            )?;
+        }
 
         let current_price = new_price;
 
         listing.price = new_price;
-        listing.history.push(TradeHistory {
+        listing.expires_at = expires_at;
+        listing.record(TradeHistory {
             price: current_price,
             timestamp: clock.unix_timestamp,
             action: TradeAction::UpdatePrice,
+            royalty: 0,
+            platform_fee: 0,
         });
 
         Ok(())
     }
 
+    /// Cancels a listing, returning a vaulted card or revoking the listing's
+    /// delegate. Once a listing has expired this still reclaims the vaulted
+    /// card or revokes the delegate `expire_listing` had to leave in place.
     pub fn cancel_listing(ctx: Context<CancelListing>) -> Result<()> {
         let clock = Clock::get()?;
+        let listing_info = ctx.accounts.listing.to_account_info();
         let listing = &mut ctx.accounts.listing;
-        require!(
-            matches!(listing.status, ListingStatus::Active),
-            CustomError::ListingNotActive
-        );
+        // The accounts already require a vaulted card or the listing's delegate
+        let was_open = matches!(listing.status, ListingStatus::Active) || listing.vaulted;
 
-        // Revoke delegate approval
-        token::revoke(CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            token::Revoke {
-                source: ctx.accounts.token_account.to_account_info(),
-                authority: ctx.accounts.seller.to_account_info(),
-            },
-        ))?;
+        if listing.vaulted {
+            let vault = ctx
+                .accounts
+                .vault
+                .as_ref()
+                .ok_or(CustomError::MissingListingVault)?;
+            let mint_key = ctx.accounts.mint.key();
+            let seeds = &[b"listing", mint_key.as_ref(), &[ctx.bumps.listing]];
+            let signer = &[&seeds[..]];
+
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Transfer {
+                        from: vault.to_account_info(),
+                        to: ctx.accounts.token_account.to_account_info(),
+                        authority: listing_info.clone(),
+                    },
+                    signer,
+                ),
+                1,
+            )?;
+            token::close_account(CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::CloseAccount {
+                    account: vault.to_account_info(),
+                    destination: ctx.accounts.seller.to_account_info(),
+                    authority: listing_info,
+                },
+                signer,
+            ))?;
+            listing.vaulted = false;
+        } else {
+            // Revoke delegate approval
+            token::revoke(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Revoke {
+                    source: ctx.accounts.token_account.to_account_info(),
+                    authority: ctx.accounts.seller.to_account_info(),
+                },
+            ))?;
+            listing.release_deposit(&listing_info, &ctx.accounts.seller.to_account_info())?;
+        }
+        if !was_open {
+            msg!("Revoked the leftover delegate of an inactive listing");
+            return Ok(());
+        }
         listing.status = ListingStatus::NotActive;
         let current_price = listing.price;
 
-        listing.history.push(TradeHistory {
+        listing.record(TradeHistory {
             price: current_price,
             timestamp: clock.unix_timestamp,
             action: TradeAction::Cancel,
+            royalty: 0,
+            platform_fee: 0,
         });
 
         Ok(())
     }
 
-    pub fn purchase_nft(ctx: Context<PurchaseNFT>) -> Result<()> {
-        let price = ctx.accounts.listing.price;
-        let buyer_balance = ctx.accounts.buyer.lamports();
-        require!(buyer_balance >= price, CustomError::InsufficientFunds);
+    pub fn purchase_nft<'info>(ctx: Context<'_, '_, '_, 'info, PurchaseNFT<'info>>) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::Purchase),
+            CustomError::ProgramPaused
+        );
 
-        let listing = &mut ctx.accounts.listing;
         require!(
-            matches!(listing.status, ListingStatus::Active),
+            matches!(ctx.accounts.listing.status, ListingStatus::Active),
             CustomError::ListingNotActive
         );
+        let clock = Clock::get()?;
+        require!(
+            !ctx.accounts.listing.is_expired(clock.unix_timestamp),
+            CustomError::ListingExpired
+        );
+        if let Some(reserved_buyer) = ctx.accounts.listing.reserved_buyer {
+            require_keys_eq!(
+                ctx.accounts.buyer.key(),
+                reserved_buyer,
+                CustomError::ListingReserved
+            );
+        }
+        let price = ctx.accounts.listing.current_price(clock.unix_timestamp)?;
 
-        // Calculate royalties
-        let royalty = price
-            .checked_mul(3)
-            .and_then(|v| v.checked_div(100))
-            .ok_or(CustomError::Overflow)?;
+        // Calculate royalties from the card's on-chain metadata
+        let metadata = load_card_metadata(&ctx.accounts.metadata, &ctx.accounts.mint.key())?;
 
-        let seller_amount = price.checked_sub(royalty).ok_or(CustomError::Overflow)?;
+        let (source, seller_account, treasury_account) = match ctx.accounts.listing.payment_mint {
+            None => {
+                require!(
+                    ctx.accounts.buyer.lamports() >= price,
+                    CustomError::InsufficientFunds
+                );
+                (
+                    PaymentSource::Lamports {
+                        payer: ctx.accounts.buyer.to_account_info(),
+                        system_program: ctx.accounts.system_program.to_account_info(),
+                    },
+                    ctx.accounts.seller.to_account_info(),
+                    ctx.accounts.treasury.to_account_info(),
+                )
+            }
+            Some(payment_mint) => {
+                require!(
+                    ctx.accounts.config.accepts_payment_mint(&payment_mint),
+                    CustomError::PaymentMintNotAccepted
+                );
+                let buyer_payment = payment_account(
+                    &ctx.accounts.buyer_payment_account,
+                    &payment_mint,
+                    &ctx.accounts.buyer.key(),
+                )?;
+                require!(buyer_payment.amount >= price, CustomError::InsufficientFunds);
+                let seller_payment = payment_account(
+                    &ctx.accounts.seller_payment_account,
+                    &payment_mint,
+                    &ctx.accounts.seller.key(),
+                )?;
+                let treasury_payment = payment_account(
+                    &ctx.accounts.treasury_payment_account,
+                    &payment_mint,
+                    &ctx.accounts.treasury.key(),
+                )?;
+                (
+                    PaymentSource::Token {
+                        from: buyer_payment.to_account_info(),
+                        authority: ctx.accounts.buyer.to_account_info(),
+                        token_program: ctx.accounts.token_program.to_account_info(),
+                        mint: payment_mint,
+                    },
+                    seller_payment.to_account_info(),
+                    treasury_payment.to_account_info(),
+                )
+            }
+        };
 
-        // Transfer funds
-        anchor_lang::system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                anchor_lang::system_program::Transfer {
-                    from: ctx.accounts.buyer.to_account_info(),
-                    to: ctx.accounts.collection_authority.to_account_info(),
-                },
-            ),
-            royalty,
+        let proceeds = settle_sale(
+            &source,
+            &metadata,
+            ctx.accounts.config.platform_fee_bps,
+            ctx.remaining_accounts,
+            &treasury_account,
+            &seller_account,
+            price,
         )?;
 
-        anchor_lang::system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                anchor_lang::system_program::Transfer {
-                    from: ctx.accounts.buyer.to_account_info(),
-                    to: ctx.accounts.seller.to_account_info(),
-                },
-            ),
-            seller_amount,
-        )?;
+        let listing = &mut ctx.accounts.listing;
 
         // Store mint key and listing info before CPI calls
         let mint_key = ctx.accounts.mint.key();
@@ -486,301 +1279,3572 @@ msg!("Rent Sysvar: {}", ctx.accounts.rent.key());
         let seeds = &[b"listing", mint_key.as_ref(), &[ctx.bumps.listing]];
         let signer = &[&seeds[..]];
 
+        // The card comes out of the listing's vault or, for delegated
+        // listings, straight out of the seller's account
+        let card_source = if listing.vaulted {
+            ctx.accounts
+                .vault
+                .as_ref()
+                .ok_or(CustomError::MissingListingVault)?
+                .to_account_info()
+        } else {
+            ctx.accounts
+                .seller_token
+                .as_ref()
+                .ok_or(CustomError::InvalidNFTOwnership)?
+                .to_account_info()
+        };
+
         token::transfer(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 token::Transfer {
-                    from: ctx.accounts.seller_token.to_account_info(),
+                    from: card_source.clone(),
                     to: ctx.accounts.buyer_token.to_account_info(),
-                    authority: listing_info,
+                    authority: listing_info.clone(),
                 },
                 signer,
             ),
             1,
         )?;
 
-        // Update listing history
-        let clock = Clock::get()?;
+        if listing.vaulted {
+            token::close_account(CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::CloseAccount {
+                    account: card_source,
+                    destination: ctx.accounts.seller.to_account_info(),
+                    authority: listing_info.clone(),
+                },
+                signer,
+            ))?;
+            listing.vaulted = false;
+        }
+        listing.release_deposit(&listing_info, &ctx.accounts.seller.to_account_info())?;
 
+        // Update listing history
         listing.status = ListingStatus::NotActive;
         listing.seller = ctx.accounts.buyer.key();
         let current_price = price;
-        listing.history.push(TradeHistory {
+        listing.record(TradeHistory {
             price: current_price,
             timestamp: clock.unix_timestamp,
             action: TradeAction::Purchase,
+            royalty: proceeds.royalty,
+            platform_fee: proceeds.platform_fee,
         });
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct CreateCollection<'info> {
-    #[account(
-        mut,
-        constraint = payer.key() == COLLECTION_AUTHORITY @ CustomError::InvalidCollectionAuthority
-    )]
-    pub payer: Signer<'info>,
+    /// Marks an expired listing `NotActive`. Anyone can call this.
+    ///
+    /// SPL Token only lets the owner revoke a delegate, so the listing PDA
+    /// stays approved on the seller's token account until they call
+    /// `cancel_listing`, which revokes it, or list again. Nothing can be bought
+    /// through it once the listing is inactive. Vaulted cards stay in the
+    /// vault until the seller cancels. The seller's prune deposit is returned.
+    pub fn expire_listing(ctx: Context<ExpireListing>) -> Result<()> {
+        let clock = Clock::get()?;
+        let listing_info = ctx.accounts.listing.to_account_info();
+        let listing = &mut ctx.accounts.listing;
+        require!(
+            matches!(listing.status, ListingStatus::Active),
+            CustomError::ListingNotActive
+        );
+        require!(
+            listing.is_expired(clock.unix_timestamp),
+            CustomError::ListingNotExpired
+        );
 
-    #[account(
-        mut,
-        constraint = collection_authority.key() == COLLECTION_AUTHORITY @ CustomError::InvalidCollectionAuthority
-    )]
-    pub collection_authority: Signer<'info>,
+        listing.release_deposit(&listing_info, &ctx.accounts.seller.to_account_info())?;
+        listing.status = ListingStatus::NotActive;
+        let current_price = listing.price;
+        listing.record(TradeHistory {
+            price: current_price,
+            timestamp: clock.unix_timestamp,
+            action: TradeAction::Expired,
+            royalty: 0,
+            platform_fee: 0,
+        });
 
-    #[account(
-        init,
-        payer = payer,
-        mint::decimals = 0,
-        mint::authority = collection_authority.key(),
-        mint::freeze_authority = collection_authority.key(),
-    )]
-    pub collection_mint: Account<'info, Mint>,
+        msg!("Listing for {} expired", listing.mint);
 
-    #[account(
-        init,
-        payer = payer,
-        associated_token::mint = collection_mint,
-        associated_token::authority = payer
-    )]
-    pub collection_token_account: Account<'info, TokenAccount>,
+        Ok(())
+    }
 
-    #[account(
-        mut,
-        address = Metadata::find_pda(&collection_mint.key()).0
-    )]
-    /// CHECK: Metaplex validated
-    pub collection_metadata: UncheckedAccount<'info>,
+    /// Marks a delegated listing `NotActive` once the seller no longer holds
+    /// the card or the listing is no longer its delegate. Anyone can call
+    /// this and is paid the deposit the seller posted when listing.
+    pub fn prune_listing(ctx: Context<PruneListing>) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+        require!(
+            matches!(listing.status, ListingStatus::Active),
+            CustomError::ListingNotActive
+        );
+        // Vaulted listings hold the card themselves and can't go stale
+        require!(!listing.vaulted, CustomError::ListingNotStale);
 
-    #[account(
-        mut,
-        address = MasterEdition::find_pda(&collection_mint.key()).0
-    )]
-    /// CHECK: Metaplex validated
-    pub collection_master_edition: UncheckedAccount<'info>,
+        // A closed or emptied token account counts as stale
+        let seller_token = &ctx.accounts.seller_token;
+        let stale = match read_token_account(seller_token) {
+            Ok(token_account) => {
+                token_account.amount < 1
+                    || token_account.delegate != COption::Some(listing.key())
+                    || token_account.delegated_amount < 1
+            }
+            Err(_) => true,
+        };
+        require!(stale, CustomError::ListingNotStale);
 
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
+        let clock = Clock::get()?;
+        let listing_info = ctx.accounts.listing.to_account_info();
+        let listing = &mut ctx.accounts.listing;
+        // The seller forfeits their deposit for leaving the listing stale
+        listing.release_deposit(&listing_info, &ctx.accounts.caller.to_account_info())?;
+        listing.status = ListingStatus::NotActive;
+        let current_price = listing.price;
+        listing.record(TradeHistory {
+            price: current_price,
+            timestamp: clock.unix_timestamp,
+            action: TradeAction::Invalidated,
+            royalty: 0,
+            platform_fee: 0,
+        });
 
-    #[account(address = mpl_token_metadata::ID)]
-    /// CHECK: Master Edition account owned by Token Metadata Program
-    pub metadata_program: UncheckedAccount<'info>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        msg!("Listing for {} invalidated", listing.mint);
 
-#[derive(Accounts)]
-pub struct MintNFT<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
+        Ok(())
+    }
+
+    pub fn create_auction(
+        ctx: Context<CreateAuction>,
+        reserve_price: u64,
+        end_at: i64,
+        min_increment_bps: u16,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::List),
+            CustomError::ProgramPaused
+        );
+        require!(reserve_price > 0, CustomError::InvalidPrice);
+        require!(
+            min_increment_bps <= MarketplaceConfig::MAX_BPS,
+            CustomError::InvalidBidIncrement
+        );
+
+        let clock = Clock::get()?;
+        require!(
+            end_at > clock.unix_timestamp
+                && end_at - clock.unix_timestamp <= Auction::MAX_DURATION,
+            CustomError::InvalidAuctionEnd
+        );
+
+        // The card stays with the seller until settlement, like a listing
+        token::approve(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Approve {
+                    to: ctx.accounts.token_account.to_account_info(),
+                    delegate: ctx.accounts.auction.to_account_info(),
+                    authority: ctx.accounts.seller.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        let auction = &mut ctx.accounts.auction;
+        auction.seller = ctx.accounts.seller.key();
+        auction.mint = ctx.accounts.mint.key();
+        auction.reserve_price = reserve_price;
+        auction.min_increment_bps = min_increment_bps;
+        auction.highest_bid = 0;
+        auction.highest_bidder = None;
+        auction.end_at = end_at;
+        auction.bump = ctx.bumps.auction;
+
+        msg!("Auction created for {}, ends at {}", auction.mint, end_at);
+
+        Ok(())
+    }
+
+    pub fn place_bid(ctx: Context<PlaceBid>, amount: u64) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::Purchase),
+            CustomError::ProgramPaused
+        );
+
+        let clock = Clock::get()?;
+        let auction = &ctx.accounts.auction;
+        require!(
+            clock.unix_timestamp < auction.end_at,
+            CustomError::AuctionEnded
+        );
+        require!(
+            ctx.accounts.bidder.key() != auction.seller,
+            CustomError::SellerCannotBid
+        );
+        require!(amount >= auction.min_next_bid()?, CustomError::BidTooLow);
+
+        // Escrow the new bid before releasing the one it replaces
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.bidder.to_account_info(),
+                    to: ctx.accounts.auction.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        if let Some(previous_bidder) = ctx.accounts.auction.highest_bidder {
+            let previous = ctx
+                .accounts
+                .previous_bidder
+                .as_ref()
+                .ok_or(CustomError::InvalidPreviousBidder)?;
+            require_keys_eq!(
+                previous.key(),
+                previous_bidder,
+                CustomError::InvalidPreviousBidder
+            );
+
+            let refund = ctx.accounts.auction.highest_bid;
+            ctx.accounts.auction.sub_lamports(refund)?;
+            previous.add_lamports(refund)?;
+        }
+
+        let auction = &mut ctx.accounts.auction;
+        auction.highest_bid = amount;
+        auction.highest_bidder = Some(ctx.accounts.bidder.key());
+
+        msg!("Bid of {} placed on {}", amount, auction.mint);
+
+        Ok(())
+    }
+
+    pub fn settle_auction<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleAuction<'info>>,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::Purchase),
+            CustomError::ProgramPaused
+        );
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= ctx.accounts.auction.end_at,
+            CustomError::AuctionNotEnded
+        );
+
+        let price = ctx.accounts.auction.highest_bid;
+        let mint_key = ctx.accounts.mint.key();
+
+        // If the seller moved the card or revoked the delegate the sale can't
+        // complete, so the winning bid goes back to the bidder instead
+        let seller_token = &ctx.accounts.seller_token;
+        if seller_token.amount != 1
+            || seller_token.delegate != COption::Some(ctx.accounts.auction.key())
+            || seller_token.delegated_amount < 1
+        {
+            ctx.accounts.auction.sub_lamports(price)?;
+            ctx.accounts.winner.add_lamports(price)?;
+            msg!("Auction for {} could not be settled, bid refunded", mint_key);
+            return Ok(());
+        }
+
+        let metadata = load_card_metadata(&ctx.accounts.metadata, &mint_key)?;
+        let source = PaymentSource::Escrow {
+            account: ctx.accounts.auction.to_account_info(),
+        };
+        let proceeds = settle_sale(
+            &source,
+            &metadata,
+            ctx.accounts.config.platform_fee_bps,
+            ctx.remaining_accounts,
+            &ctx.accounts.treasury.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            price,
+        )?;
+
+        let seeds = &[b"auction", mint_key.as_ref(), &[ctx.accounts.auction.bump]];
+        let signer = &[&seeds[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.seller_token.to_account_info(),
+                    to: ctx.accounts.winner_token.to_account_info(),
+                    authority: ctx.accounts.auction.to_account_info(),
+                },
+                signer,
+            ),
+            1,
+        )?;
+
+        // Record the sale in the card's listing history
+        let listing_info = ctx.accounts.listing.to_account_info();
+        ctx.accounts.listing.hand_over(
+            &listing_info,
+            &ctx.accounts.seller.to_account_info(),
+            ctx.accounts.winner.key(),
+            mint_key,
+            TradeHistory {
+                price,
+                timestamp: clock.unix_timestamp,
+                action: TradeAction::AuctionSettled,
+                royalty: proceeds.royalty,
+                platform_fee: proceeds.platform_fee,
+            },
+        )?;
+
+        msg!("Auction for {} settled at {}", mint_key, price);
+
+        Ok(())
+    }
+
+    pub fn cancel_auction(ctx: Context<CancelAuction>) -> Result<()> {
+        require!(
+            ctx.accounts.auction.highest_bidder.is_none(),
+            CustomError::AuctionHasBids
+        );
+
+        if ctx.accounts.token_account.delegate == COption::Some(ctx.accounts.auction.key()) {
+            token::revoke(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Revoke {
+                    source: ctx.accounts.token_account.to_account_info(),
+                    authority: ctx.accounts.seller.to_account_info(),
+                },
+            ))?;
+        }
+
+        msg!("Auction for {} cancelled", ctx.accounts.auction.mint);
+
+        Ok(())
+    }
+
+    pub fn make_offer(ctx: Context<MakeOffer>, amount: u64) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::Purchase),
+            CustomError::ProgramPaused
+        );
+        require!(amount > 0, CustomError::InvalidPrice);
+
+        // The offer account holds the bid until it is accepted or cancelled
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.bidder.to_account_info(),
+                    to: ctx.accounts.offer.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let offer = &mut ctx.accounts.offer;
+        offer.bidder = ctx.accounts.bidder.key();
+        offer.mint = ctx.accounts.mint.key();
+        offer.amount = amount;
+        offer.created_at = Clock::get()?.unix_timestamp;
+        offer.bump = ctx.bumps.offer;
+
+        msg!("Offer of {} made on {}", amount, offer.mint);
+
+        Ok(())
+    }
+
+    pub fn cancel_offer(ctx: Context<CancelOffer>) -> Result<()> {
+        // Closing the offer returns the escrowed bid along with the rent
+        msg!("Offer on {} cancelled", ctx.accounts.offer.mint);
+
+        Ok(())
+    }
+
+    pub fn accept_offer<'info>(ctx: Context<'_, '_, '_, 'info, AcceptOffer<'info>>) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::Purchase),
+            CustomError::ProgramPaused
+        );
+
+        let price = ctx.accounts.offer.amount;
+        let mint_key = ctx.accounts.mint.key();
+
+        let metadata = load_card_metadata(&ctx.accounts.metadata, &mint_key)?;
+        let source = PaymentSource::Escrow {
+            account: ctx.accounts.offer.to_account_info(),
+        };
+        let proceeds = settle_sale(
+            &source,
+            &metadata,
+            ctx.accounts.config.platform_fee_bps,
+            ctx.remaining_accounts,
+            &ctx.accounts.treasury.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            price,
+        )?;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.seller_token.to_account_info(),
+                    to: ctx.accounts.bidder_token.to_account_info(),
+                    authority: ctx.accounts.seller.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        let listing_info = ctx.accounts.listing.to_account_info();
+        ctx.accounts.listing.hand_over(
+            &listing_info,
+            &ctx.accounts.seller.to_account_info(),
+            ctx.accounts.bidder.key(),
+            mint_key,
+            TradeHistory {
+                price,
+                timestamp: Clock::get()?.unix_timestamp,
+                action: TradeAction::OfferAccepted,
+                royalty: proceeds.royalty,
+                platform_fee: proceeds.platform_fee,
+            },
+        )?;
+
+        msg!("Offer of {} on {} accepted", price, mint_key);
+
+        Ok(())
+    }
+
+    pub fn make_collection_offer(
+        ctx: Context<MakeCollectionOffer>,
+        id: u64,
+        amount: u64,
+        filter: CardFilter,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::Purchase),
+            CustomError::ProgramPaused
+        );
+        require!(amount > 0, CustomError::InvalidPrice);
+
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.bidder.to_account_info(),
+                    to: ctx.accounts.offer.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let offer = &mut ctx.accounts.offer;
+        offer.id = id;
+        offer.bidder = ctx.accounts.bidder.key();
+        offer.collection_mint = ctx.accounts.collection_registry.collection_mint;
+        offer.amount = amount;
+        offer.filter = filter;
+        offer.created_at = Clock::get()?.unix_timestamp;
+        offer.bump = ctx.bumps.offer;
+
+        msg!(
+            "Collection offer {} of {} made on {}",
+            id,
+            amount,
+            offer.collection_mint
+        );
+
+        Ok(())
+    }
+
+    pub fn cancel_collection_offer(ctx: Context<CancelCollectionOffer>) -> Result<()> {
+        msg!("Collection offer {} cancelled", ctx.accounts.offer.id);
+
+        Ok(())
+    }
+
+    pub fn accept_collection_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, AcceptCollectionOffer<'info>>,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::Purchase),
+            CustomError::ProgramPaused
+        );
+
+        let offer = &ctx.accounts.offer;
+        let price = offer.amount;
+        let mint_key = ctx.accounts.mint.key();
+
+        // The card must be a verified member of the offer's collection
+        let metadata = load_card_metadata(&ctx.accounts.metadata, &mint_key)?;
+        require!(
+            metadata
+                .collection
+                .as_ref()
+                .is_some_and(|c| c.verified && c.key == offer.collection_mint),
+            CustomError::CardNotInCollection
+        );
+        require!(
+            offer.filter.matches(&ctx.accounts.stats),
+            CustomError::CardFilterMismatch
+        );
+
+        let source = PaymentSource::Escrow {
+            account: ctx.accounts.offer.to_account_info(),
+        };
+        let proceeds = settle_sale(
+            &source,
+            &metadata,
+            ctx.accounts.config.platform_fee_bps,
+            ctx.remaining_accounts,
+            &ctx.accounts.treasury.to_account_info(),
+            &ctx.accounts.seller.to_account_info(),
+            price,
+        )?;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.seller_token.to_account_info(),
+                    to: ctx.accounts.bidder_token.to_account_info(),
+                    authority: ctx.accounts.seller.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        let listing_info = ctx.accounts.listing.to_account_info();
+        ctx.accounts.listing.hand_over(
+            &listing_info,
+            &ctx.accounts.seller.to_account_info(),
+            ctx.accounts.bidder.key(),
+            mint_key,
+            TradeHistory {
+                price,
+                timestamp: Clock::get()?.unix_timestamp,
+                action: TradeAction::OfferAccepted,
+                royalty: proceeds.royalty,
+                platform_fee: proceeds.platform_fee,
+            },
+        )?;
+
+        msg!(
+            "Collection offer {} accepted with {}",
+            ctx.accounts.offer.id,
+            mint_key
+        );
+
+        Ok(())
+    }
+
+    /// Lists several cards for one price.
+    ///
+    /// `remaining_accounts` holds the seller's token account and the listing
+    /// PDA for each card; the bundle PDA is approved as delegate on every
+    /// token account. Cards with an active listing are rejected, since the
+    /// approval would replace the listing's delegate.
+    pub fn create_bundle_listing<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateBundleListing<'info>>,
+        id: u64,
+        price: u64,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::List),
+            CustomError::ProgramPaused
+        );
+        require!(price > 0, CustomError::InvalidPrice);
+        let cards = ctx.remaining_accounts.chunks_exact(2);
+        require!(
+            cards.remainder().is_empty() && (2..=BundleListing::MAX_CARDS).contains(&cards.len()),
+            CustomError::InvalidBundleSize
+        );
+
+        let mut mints = Vec::with_capacity(cards.len());
+        for card in cards {
+            let (token_account_info, listing_info) = (&card[0], &card[1]);
+            let token_account = read_token_account(token_account_info)?;
+            require_keys_eq!(
+                token_account.owner,
+                ctx.accounts.seller.key(),
+                CustomError::NotNFTOwner
+            );
+            require!(token_account.amount == 1, CustomError::InvalidNFTOwnership);
+            require!(
+                !mints.contains(&token_account.mint),
+                CustomError::InvalidBundleAccounts
+            );
+            require!(
+                !is_listed(listing_info, &token_account.mint)?,
+                CustomError::ListingAlreadyActive
+            );
+
+            token::approve(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Approve {
+                        to: token_account_info.clone(),
+                        delegate: ctx.accounts.bundle.to_account_info(),
+                        authority: ctx.accounts.seller.to_account_info(),
+                    },
+                ),
+                1,
+            )?;
+            mints.push(token_account.mint);
+        }
+
+        let bundle = &mut ctx.accounts.bundle;
+        bundle.id = id;
+        bundle.seller = ctx.accounts.seller.key();
+        bundle.mints = mints;
+        bundle.price = price;
+        bundle.created_at = Clock::get()?.unix_timestamp;
+        bundle.bump = ctx.bumps.bundle;
+
+        msg!("Bundle {} listed with {} cards", id, bundle.mints.len());
+
+        Ok(())
+    }
+
+    /// Buys every card in a bundle at once.
+    ///
+    /// For each card, in bundle order, `remaining_accounts` holds the mint,
+    /// its metadata, the seller's token account, the buyer's associated token
+    /// account, the card's listing PDA and then the card's verified creators.
+    /// The price is split evenly across the cards (dust to the first) and
+    /// royalties and fees are settled per card.
+    pub fn purchase_bundle<'info>(
+        ctx: Context<'_, '_, '_, 'info, PurchaseBundle<'info>>,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::Purchase),
+            CustomError::ProgramPaused
+        );
+
+        let bundle = &ctx.accounts.bundle;
+        let price = bundle.price;
+        require!(
+            ctx.accounts.buyer.lamports() >= price,
+            CustomError::InsufficientFunds
+        );
+
+        let card_count = bundle.mints.len() as u64;
+        let card_price = price / card_count;
+        let first_card_price = price - card_price * (card_count - 1);
+
+        let seller_key = bundle.seller;
+        let id_bytes = bundle.id.to_le_bytes();
+        let seeds = &[
+            b"bundle".as_ref(),
+            seller_key.as_ref(),
+            id_bytes.as_ref(),
+            &[bundle.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let source = PaymentSource::Lamports {
+            payer: ctx.accounts.buyer.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        };
+        let bundle_info = ctx.accounts.bundle.to_account_info();
+        let mut rest = ctx.remaining_accounts;
+
+        for (i, mint_key) in ctx.accounts.bundle.mints.iter().enumerate() {
+            require!(rest.len() >= 5, CustomError::InvalidBundleAccounts);
+            let (card, tail) = rest.split_at(5);
+            let (mint, metadata_info, seller_token, buyer_token, listing_info) =
+                (&card[0], &card[1], &card[2], &card[3], &card[4]);
+
+            require_keys_eq!(mint.key(), *mint_key, CustomError::InvalidBundleAccounts);
+            require_keys_eq!(
+                metadata_info.key(),
+                Metadata::find_pda(mint_key).0,
+                CustomError::MetadataMintMismatch
+            );
+            require_keys_eq!(
+                *metadata_info.owner,
+                mpl_token_metadata::ID,
+                CustomError::IncorrectOwner
+            );
+            let metadata = load_card_metadata(metadata_info, mint_key)?;
+
+            // The seller must still hold the card with the bundle as delegate
+            let seller_account = read_token_account(seller_token)?;
+            require!(
+                seller_account.mint == *mint_key
+                    && seller_account.owner == seller_key
+                    && seller_account.amount == 1
+                    && seller_account.delegate == COption::Some(bundle_info.key())
+                    && seller_account.delegated_amount >= 1,
+                CustomError::InvalidBundleAccounts
+            );
+
+            require_keys_eq!(
+                buyer_token.key(),
+                associated_token::get_associated_token_address(
+                    &ctx.accounts.buyer.key(),
+                    mint_key
+                ),
+                CustomError::InvalidBundleAccounts
+            );
+
+            let creator_count = metadata
+                .creators
+                .as_ref()
+                .map_or(0, |creators| creators.iter().filter(|c| c.verified).count());
+            require!(tail.len() >= creator_count, CustomError::InvalidCreatorAccounts);
+            let (creator_accounts, tail) = tail.split_at(creator_count);
+            rest = tail;
+
+            let sale_price = if i == 0 { first_card_price } else { card_price };
+            let proceeds = settle_sale(
+                &source,
+                &metadata,
+                ctx.accounts.config.platform_fee_bps,
+                creator_accounts,
+                &ctx.accounts.treasury.to_account_info(),
+                &ctx.accounts.seller.to_account_info(),
+                sale_price,
+            )?;
+
+            associated_token::create_idempotent(CpiContext::new(
+                ctx.accounts.associated_token_program.to_account_info(),
+                associated_token::Create {
+                    payer: ctx.accounts.buyer.to_account_info(),
+                    associated_token: buyer_token.clone(),
+                    authority: ctx.accounts.buyer.to_account_info(),
+                    mint: mint.clone(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+            ))?;
+
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Transfer {
+                        from: seller_token.clone(),
+                        to: buyer_token.clone(),
+                        authority: bundle_info.clone(),
+                    },
+                    signer,
+                ),
+                1,
+            )?;
+
+            let mut listing = load_listing(
+                listing_info,
+                mint_key,
+                &ctx.accounts.buyer.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
+            listing.hand_over(
+                listing_info,
+                &ctx.accounts.seller.to_account_info(),
+                ctx.accounts.buyer.key(),
+                *mint_key,
+                TradeHistory {
+                    price: sale_price,
+                    timestamp: Clock::get()?.unix_timestamp,
+                    action: TradeAction::Purchase,
+                    royalty: proceeds.royalty,
+                    platform_fee: proceeds.platform_fee,
+                },
+            )?;
+            listing.try_serialize(&mut &mut listing_info.data.borrow_mut()[..])?;
+        }
+        require!(rest.is_empty(), CustomError::InvalidBundleAccounts);
+
+        msg!("Bundle {} purchased for {}", ctx.accounts.bundle.id, price);
+
+        Ok(())
+    }
+
+    /// Closes a bundle listing. `remaining_accounts` may hold the seller's
+    /// token accounts for the bundled cards to revoke the bundle's delegate.
+    pub fn cancel_bundle_listing<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelBundleListing<'info>>,
+    ) -> Result<()> {
+        let bundle_key = ctx.accounts.bundle.key();
+        for token_account_info in ctx.remaining_accounts.iter() {
+            let token_account = read_token_account(token_account_info)?;
+            if token_account.delegate != COption::Some(bundle_key) {
+                continue;
+            }
+
+            token::revoke(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Revoke {
+                    source: token_account_info.clone(),
+                    authority: ctx.accounts.seller.to_account_info(),
+                },
+            ))?;
+        }
+
+        msg!("Bundle {} cancelled", ctx.accounts.bundle.id);
+
+        Ok(())
+    }
+
+    /// Proposes a card-for-card swap with `counterparty`.
+    ///
+    /// `remaining_accounts` holds the proposer's token account for each
+    /// offered card; the swap PDA is approved as delegate on each of them and
+    /// holds the proposer's SOL sweetener until the swap closes.
+    pub fn propose_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, ProposeSwap<'info>>,
+        id: u64,
+        counterparty: Pubkey,
+        requested_mints: Vec<Pubkey>,
+        proposer_sweetener: u64,
+        counterparty_sweetener: u64,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::List),
+            CustomError::ProgramPaused
+        );
+        require!(
+            counterparty != ctx.accounts.proposer.key(),
+            CustomError::InvalidSwapCounterparty
+        );
+        require!(
+            (1..=Swap::MAX_CARDS_PER_SIDE).contains(&ctx.remaining_accounts.len())
+                && (1..=Swap::MAX_CARDS_PER_SIDE).contains(&requested_mints.len()),
+            CustomError::InvalidSwapSize
+        );
+
+        let mut offered_mints = Vec::with_capacity(ctx.remaining_accounts.len());
+        for token_account_info in ctx.remaining_accounts.iter() {
+            let token_account = read_token_account(token_account_info)?;
+            require_keys_eq!(
+                token_account.owner,
+                ctx.accounts.proposer.key(),
+                CustomError::NotNFTOwner
+            );
+            require!(token_account.amount == 1, CustomError::InvalidNFTOwnership);
+            require!(
+                !offered_mints.contains(&token_account.mint),
+                CustomError::InvalidSwapAccounts
+            );
+
+            token::approve(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Approve {
+                        to: token_account_info.clone(),
+                        delegate: ctx.accounts.swap.to_account_info(),
+                        authority: ctx.accounts.proposer.to_account_info(),
+                    },
+                ),
+                1,
+            )?;
+            offered_mints.push(token_account.mint);
+        }
+
+        for (i, mint) in requested_mints.iter().enumerate() {
+            require!(
+                !requested_mints[..i].contains(mint) && !offered_mints.contains(mint),
+                CustomError::InvalidSwapAccounts
+            );
+        }
+
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.proposer.to_account_info(),
+                    to: ctx.accounts.swap.to_account_info(),
+                },
+            ),
+            proposer_sweetener,
+        )?;
+
+        let swap = &mut ctx.accounts.swap;
+        swap.id = id;
+        swap.proposer = ctx.accounts.proposer.key();
+        swap.counterparty = counterparty;
+        swap.offered_mints = offered_mints;
+        swap.requested_mints = requested_mints;
+        swap.proposer_sweetener = proposer_sweetener;
+        swap.counterparty_sweetener = counterparty_sweetener;
+        swap.created_at = Clock::get()?.unix_timestamp;
+        swap.bump = ctx.bumps.swap;
+
+        msg!("Swap {} proposed to {}", id, counterparty);
+
+        Ok(())
+    }
+
+    /// Accepts a swap, moving every card and sweetener in one instruction.
+    ///
+    /// For each offered card and then each requested card, in swap order,
+    /// `remaining_accounts` holds the mint, the current holder's token
+    /// account, the receiver's associated token account and the card's
+    /// listing PDA, which records the swap for provenance. The counterparty
+    /// delegates the requested cards to the swap PDA here, so every card on
+    /// both sides moves under the swap's own signature.
+    pub fn accept_swap<'info>(ctx: Context<'_, '_, '_, 'info, AcceptSwap<'info>>) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PauseScope::Purchase),
+            CustomError::ProgramPaused
+        );
+
+        let swap = &ctx.accounts.swap;
+        let proposer_key = swap.proposer;
+        let id_bytes = swap.id.to_le_bytes();
+        let seeds = &[
+            b"swap".as_ref(),
+            proposer_key.as_ref(),
+            id_bytes.as_ref(),
+            &[swap.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let swap_info = ctx.accounts.swap.to_account_info();
+        let proposer_info = ctx.accounts.proposer.to_account_info();
+        let counterparty_info = ctx.accounts.counterparty.to_account_info();
+        let sides = [
+            (&swap.offered_mints, &proposer_info, &counterparty_info, true),
+            (&swap.requested_mints, &counterparty_info, &proposer_info, false),
+        ];
+
+        let mut rest = ctx.remaining_accounts;
+        for (mints, holder, receiver, proposed) in sides {
+            for mint_key in mints.iter() {
+                require!(rest.len() >= 4, CustomError::InvalidSwapAccounts);
+                let (card, tail) = rest.split_at(4);
+                rest = tail;
+                let (mint, from, to, listing_info) = (&card[0], &card[1], &card[2], &card[3]);
+
+                require_keys_eq!(mint.key(), *mint_key, CustomError::InvalidSwapAccounts);
+                let from_account = read_token_account(from)?;
+                require!(
+                    from_account.mint == *mint_key
+                        && from_account.owner == holder.key()
+                        && from_account.amount == 1,
+                    CustomError::InvalidSwapAccounts
+                );
+                if proposed {
+                    require!(
+                        from_account.delegate == COption::Some(swap_info.key())
+                            && from_account.delegated_amount >= 1,
+                        CustomError::InvalidSwapAccounts
+                    );
+                } else {
+                    token::approve(
+                        CpiContext::new(
+                            ctx.accounts.token_program.to_account_info(),
+                            token::Approve {
+                                to: from.clone(),
+                                delegate: swap_info.clone(),
+                                authority: counterparty_info.clone(),
+                            },
+                        ),
+                        1,
+                    )?;
+                }
+                require_keys_eq!(
+                    to.key(),
+                    associated_token::get_associated_token_address(&receiver.key(), mint_key),
+                    CustomError::InvalidSwapAccounts
+                );
+
+                associated_token::create_idempotent(CpiContext::new(
+                    ctx.accounts.associated_token_program.to_account_info(),
+                    associated_token::Create {
+                        payer: counterparty_info.clone(),
+                        associated_token: to.clone(),
+                        authority: receiver.clone(),
+                        mint: mint.clone(),
+                        system_program: ctx.accounts.system_program.to_account_info(),
+                        token_program: ctx.accounts.token_program.to_account_info(),
+                    },
+                ))?;
+
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        token::Transfer {
+                            from: from.clone(),
+                            to: to.clone(),
+                            authority: swap_info.clone(),
+                        },
+                        signer,
+                    ),
+                    1,
+                )?;
+
+                let mut listing = load_listing(
+                    listing_info,
+                    mint_key,
+                    &counterparty_info,
+                    &ctx.accounts.system_program.to_account_info(),
+                )?;
+                listing.hand_over(
+                    listing_info,
+                    holder,
+                    receiver.key(),
+                    *mint_key,
+                    TradeHistory {
+                        price: 0,
+                        timestamp: Clock::get()?.unix_timestamp,
+                        action: TradeAction::Swapped,
+                        royalty: 0,
+                        platform_fee: 0,
+                    },
+                )?;
+                listing.try_serialize(&mut &mut listing_info.data.borrow_mut()[..])?;
+            }
+        }
+        require!(rest.is_empty(), CustomError::InvalidSwapAccounts);
+
+        // The proposer's sweetener is escrowed in the swap; the rest of the
+        // swap's lamports go back to the proposer when it closes
+        let proposer_sweetener = swap.proposer_sweetener;
+        if proposer_sweetener > 0 {
+            ctx.accounts.swap.sub_lamports(proposer_sweetener)?;
+            ctx.accounts.counterparty.add_lamports(proposer_sweetener)?;
+        }
+
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: counterparty_info,
+                    to: proposer_info,
+                },
+            ),
+            ctx.accounts.swap.counterparty_sweetener,
+        )?;
+
+        msg!("Swap {} accepted", ctx.accounts.swap.id);
+
+        Ok(())
+    }
+
+    /// Closes a swap proposal, refunding the proposer's sweetener.
+    /// `remaining_accounts` may hold the proposer's token accounts for the
+    /// offered cards to revoke the swap's delegate.
+    pub fn cancel_swap<'info>(ctx: Context<'_, '_, '_, 'info, CancelSwap<'info>>) -> Result<()> {
+        let swap_key = ctx.accounts.swap.key();
+        for token_account_info in ctx.remaining_accounts.iter() {
+            let token_account = read_token_account(token_account_info)?;
+            if token_account.delegate != COption::Some(swap_key) {
+                continue;
+            }
+
+            token::revoke(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Revoke {
+                    source: token_account_info.clone(),
+                    authority: ctx.accounts.proposer.to_account_info(),
+                },
+            ))?;
+        }
+
+        msg!("Swap {} cancelled", ctx.accounts.swap.id);
+
+        Ok(())
+    }
+}
+
+/// Sale terms shared by delegated and vaulted listings.
+struct ListingTerms {
+    price: u64,
+    payment_mint: Option<Pubkey>,
+    dutch: Option<DutchPricing>,
+    expires_at: Option<i64>,
+    reserved_buyer: Option<Pubkey>,
+}
+
+impl ListingTerms {
+    fn validate(&self, config: &MarketplaceConfig, now: i64) -> Result<()> {
+        require!(self.price > 0, CustomError::InvalidPrice);
+        // For Dutch listings `price` is the starting price
+        if let Some(dutch) = &self.dutch {
+            dutch.validate(self.price)?;
+        }
+        // Listings without a payment mint are priced in lamports
+        if let Some(payment_mint) = self.payment_mint {
+            require!(
+                config.accepts_payment_mint(&payment_mint),
+                CustomError::PaymentMintNotAccepted
+            );
+        }
+        ListingData::validate_expiry(self.expires_at, now)
+    }
+}
+
+/// Terms of a self-served mint, resolved from the collection's sale settings.
+#[derive(Clone, Copy)]
+struct PublicMintTerms {
+    price: u64,
+    phase: Option<usize>,
+}
+
+/// Runs the shared card minting flow for `mint_nft` and `mint_nft_allowlisted`.
+///
+/// `public_mint` is `None` for mints co-signed by the collection authority and
+/// `Some(terms)` for self-served mints signed by the registry PDA instead.
+fn process_mint<'info>(
+    accounts: &mut MintNFT<'info>,
+    bumps: &MintNFTBumps,
+    name: String,
+    symbol: String,
+    uri: String,
+    stats: CardStats,
+    public_mint: Option<PublicMintTerms>,
+) -> Result<()> {
+    require!(
+        !accounts.config.is_paused(PauseScope::Mint),
+        CustomError::ProgramPaused
+    );
+
+    // Input validation
+    require!(name.len() <= 32, CustomError::NameTooLong);
+    require!(symbol.len() <= 10, CustomError::SymbolTooLong);
+    require!(uri.len() <= 200, CustomError::UriTooLong);
+    require!(stats.attack <= 100, CustomError::InvalidStats);
+    require!(stats.defense <= 100, CustomError::InvalidStats);
+
+    // Supply caps apply to every mint, co-signed or not
+    let registry = &mut accounts.collection_registry;
+    registry.minted = registry.minted.checked_add(1).ok_or(CustomError::Overflow)?;
+    require!(
+        registry.max_supply == 0 || registry.minted <= registry.max_supply,
+        CustomError::MaxSupplyReached
+    );
+
+    let rarity = stats.rarity.index();
+    registry.rarity_minted[rarity] = registry.rarity_minted[rarity]
+        .checked_add(1)
+        .ok_or(CustomError::Overflow)?;
+    require!(
+        registry.rarity_caps[rarity] == 0
+            || registry.rarity_minted[rarity] <= registry.rarity_caps[rarity],
+        CustomError::RaritySupplyReached
+    );
+
+    // Public mints are self-served: the payer is charged the resolved price,
+    // counts against the wallet's mint limits, and the registry PDA signs as
+    // update authority, verified creator and delegated collection authority
+    // in place of the collection authority.
+    let collection_mint_key = accounts.collection_mint.key();
+    let registry_bump = [accounts.collection_registry.bump];
+    let registry_seeds: &[&[u8]] = &[b"collection", collection_mint_key.as_ref(), &registry_bump];
+
+    let (card_authority, card_authority_info) = match public_mint {
+        None => {
+            let authority = accounts
+                .collection_authority
+                .as_ref()
+                .ok_or(CustomError::InvalidCollectionAuthority)?;
+            (authority.key(), authority.to_account_info())
+        }
+        Some(terms) => {
+            let registry = &accounts.collection_registry;
+            let counter = accounts
+                .mint_counter
+                .as_mut()
+                .ok_or(CustomError::MissingMintCounter)?;
+            counter.bump = bumps.mint_counter.ok_or(CustomError::MissingMintCounter)?;
+
+            // Per-phase counts only apply to the phase schedule they were made under
+            if counter.phases_version != registry.phases_version {
+                counter.phase_minted = [0; CollectionRegistry::MAX_PHASES];
+                counter.phases_version = registry.phases_version;
+            }
+
+            counter.minted = counter.minted.checked_add(1).ok_or(CustomError::Overflow)?;
+            require!(
+                registry.max_per_wallet == 0 || counter.minted <= registry.max_per_wallet,
+                CustomError::MintLimitExceeded
+            );
+
+            if let Some(phase) = terms.phase {
+                let phase_minted = &mut counter.phase_minted[phase];
+                *phase_minted = phase_minted.checked_add(1).ok_or(CustomError::Overflow)?;
+                let phase_limit = registry.phases[phase].max_per_wallet;
+                require!(
+                    phase_limit == 0 || *phase_minted <= phase_limit,
+                    CustomError::MintLimitExceeded
+                );
+            }
+
+            if terms.price > 0 {
+                anchor_lang::system_program::transfer(
+                    CpiContext::new(
+                        accounts.system_program.to_account_info(),
+                        anchor_lang::system_program::Transfer {
+                            from: accounts.payer.to_account_info(),
+                            to: accounts.collection_registry.to_account_info(),
+                        },
+                    ),
+                    terms.price,
+                )?;
+
+                msg!("Charged mint price: {}", terms.price);
+            }
+
+            (
+                accounts.collection_registry.key(),
+                accounts.collection_registry.to_account_info(),
+            )
+        }
+    };
+    let authority_signer: &[&[&[u8]]] = if public_mint.is_some() {
+        &[registry_seeds]
+    } else {
+        &[]
+    };
+
+    // Mint token
+    token::mint_to(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            token::MintTo {
+                mint: accounts.mint.to_account_info(),
+                to: accounts.token_account.to_account_info(),
+                authority: accounts.payer.to_account_info(),
+            },
+        ),
+        1,
+    )?;
+
+    msg!("Finished Minting");
+
+    // Create metadata
+    let creators = vec![Creator {
+        address: card_authority,
+        verified: true,
+        share: 100,
+    }];
+
+    let metadata_ix = CreateMetadataAccountV3Builder::new()
+        .metadata(accounts.metadata.key())
+        .mint(accounts.mint.key())
+        .mint_authority(accounts.payer.key())
+        .payer(accounts.payer.key())
+        .update_authority(card_authority, true)
+        .data(DataV2 {
+            name,
+            symbol,
+            uri,
+            seller_fee_basis_points: accounts.collection_registry.royalty_bps,
+            creators: Some(creators),
+            collection: Some(Collection {
+                key: accounts.collection_mint.key(),
+                verified: false, // Will be verified in the next step
+            }),
+            uses: None,
+        }).is_mutable(false)
+        .instruction();
+
+    invoke_signed(
+        &metadata_ix,
+        &[
+            accounts.metadata.to_account_info(),
+            accounts.mint.to_account_info(),
+            accounts.payer.to_account_info(),
+            card_authority_info.clone(),
+            accounts.metadata_program.to_account_info(),
+            accounts.token_program.to_account_info(),
+            accounts.system_program.to_account_info(),
+            accounts.rent.to_account_info(),
+        ],
+        authority_signer,
+    )?;
+
+    msg!("Finished Metadata assignment");
+
+    let master_edition_ix = CreateMasterEditionV3Builder::new()
+        .edition(accounts.master_edition.key())
+        .mint(accounts.mint.key())
+        .update_authority(card_authority)
+        .mint_authority(accounts.payer.key())
+        .metadata(accounts.metadata.key())
+        .payer(accounts.payer.key())
+        .max_supply(0)
+        .instruction();
+
+    invoke_signed(
+        &master_edition_ix,
+        &[
+            accounts.master_edition.to_account_info(),
+            accounts.metadata.to_account_info(),
+            accounts.mint.to_account_info(),
+            accounts.payer.to_account_info(),
+            card_authority_info.clone(),
+            accounts.metadata_program.to_account_info(),
+            accounts.token_program.to_account_info(),
+            accounts.system_program.to_account_info(),
+            accounts.rent.to_account_info(),
+        ],
+        authority_signer,
+    )?;
+    msg!("Finished Master Eddition assignment");
+
+    msg!("Starting verification process");
+    msg!("Token Metadata Program ID: {}", mpl_token_metadata::ID);
+    msg!(
+        "Metadata account: {} owned by {}",
+        accounts.metadata.key(),
+        accounts.metadata.owner
+    );
+    msg!(
+        "Collection metadata: {} owned by {}",
+        accounts.collection_metadata.key(),
+        accounts.collection_metadata.owner
+    );
+    msg!(
+        "Collection master edition: {} owned by {}",
+        accounts.collection_master_edition.key(),
+        accounts.collection_master_edition.owner
+    );
+    msg!("Collection authority: {}", card_authority);
+
+    // Verify the collection
+    let authority_record = public_mint.map(|_| accounts.collection_authority_record.key());
+    let verify_ix = VerifyCollectionBuilder::new()
+        .metadata(accounts.metadata.key())
+        .collection_authority(card_authority)
+        .payer(accounts.payer.key())
+        .collection_mint(accounts.collection_mint.key())
+        .collection(accounts.collection_metadata.key())
+        .collection_master_edition_account(accounts.collection_master_edition.key())
+        .collection_authority_record(authority_record)
+        .instruction();
+
+    invoke_signed(
+        &verify_ix,
+        &[
+            accounts.metadata.to_account_info(),
+            card_authority_info,
+            accounts.payer.to_account_info(),
+            accounts.collection_mint.to_account_info(),
+            accounts.collection_metadata.to_account_info(),
+            accounts.collection_master_edition.to_account_info(),
+            accounts.collection_authority_record.to_account_info(),
+        ],
+        authority_signer,
+    )?;
+
+    msg!("Finished Verification");
+
+    let stats_account = &mut accounts.stats_account;
+    stats_account.mint = accounts.mint.key();
+    stats_account.attack = stats.attack;
+    stats_account.defense = stats.defense;
+    stats_account.element = stats.element;
+    stats_account.rarity = stats.rarity;
+
+    msg!("Finished Stats Account assignment");
+
+    // Revoke mint authority (set to None)
+    token::set_authority(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            token::SetAuthority {
+                current_authority: accounts.payer.to_account_info(),
+                account_or_mint: accounts.mint.to_account_info(),
+            },
+        ),
+        AuthorityType::MintTokens, // Change this from token::AuthorityType to just AuthorityType
+        None,
+    )?;
+
+    token::set_authority(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            token::SetAuthority {
+                current_authority: accounts.payer.to_account_info(),
+                account_or_mint: accounts.mint.to_account_info(),
+            },
+        ),
+        AuthorityType::FreezeAccount,
+        None,
+    )?;
+
+    msg!("Finished Authority assignment");
+
+    Ok(())
+}
+
+/// Leaf committed to by the allowlist merkle tree: the wallet and the number
+/// of cards it may mint (`0` for no per-wallet cap).
+fn allowlist_leaf(wallet: &Pubkey, allowance: u32) -> [u8; 32] {
+    keccak::hashv(&[wallet.as_ref(), &allowance.to_le_bytes()]).0
+}
+
+/// Verifies a keccak merkle proof built with sorted sibling pairs.
+fn verify_merkle_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+    let mut computed = leaf;
+    for node in proof {
+        computed = if computed <= *node {
+            keccak::hashv(&[&computed, node]).0
+        } else {
+            keccak::hashv(&[node, &computed]).0
+        };
+    }
+    computed == root
+}
+
+/// Checks that `signer` may perform `action`. Without an admin set this is the
+/// config authority; once one is configured, the signer is only the fee payer
+/// and may be anyone, but the caller must pass a proposal for exactly this
+/// action with enough approvals. The proposal is then closed to its proposer.
+fn authorize_admin_action<'info>(
+    config: &MarketplaceConfig,
+    signer: &Pubkey,
+    admin_set: Option<&Account<'info, AdminSet>>,
+    proposal: Option<&mut Account<'info, Proposal>>,
+    proposer: Option<AccountInfo<'info>>,
+    action: AdminAction,
+) -> Result<()> {
+    if !config.multisig {
+        require_keys_eq!(*signer, config.authority, CustomError::InvalidConfigAuthority);
+        return Ok(());
+    }
+
+    let admin_set = admin_set.ok_or(CustomError::ProposalRequired)?;
+    let proposal = proposal.ok_or(CustomError::ProposalRequired)?;
+    require!(!proposal.executed, CustomError::ProposalAlreadyExecuted);
+    require!(proposal.action == action, CustomError::ProposalActionMismatch);
+
+    // Approvals from signers removed since the proposal was created don't count
+    let approvals = proposal
+        .approvals
+        .iter()
+        .filter(|approver| admin_set.signers.contains(approver))
+        .count();
+    require!(
+        approvals >= admin_set.threshold as usize,
+        CustomError::ProposalNotApproved
+    );
+
+    // The proposal is spent, so its rent goes back to whoever opened it
+    let proposer = proposer.ok_or(CustomError::InvalidProposer)?;
+    require_keys_eq!(proposer.key(), proposal.proposer, CustomError::InvalidProposer);
+    proposal.executed = true;
+    proposal.close(proposer)?;
+
+    Ok(())
+}
+
+/// Checks that `signer` may perform `action` on `registry`. Without an admin
+/// set this is the collection's own authority; once one is configured the
+/// action needs an approved proposal like any other admin action.
+fn authorize_collection_action<'info>(
+    config: &MarketplaceConfig,
+    registry: &CollectionRegistry,
+    signer: &Pubkey,
+    admin_set: Option<&Account<'info, AdminSet>>,
+    proposal: Option<&mut Account<'info, Proposal>>,
+    proposer: Option<AccountInfo<'info>>,
+    action: AdminAction,
+) -> Result<()> {
+    if config.multisig {
+        return authorize_admin_action(config, signer, admin_set, proposal, proposer, action);
+    }
+    require_keys_eq!(
+        *signer,
+        registry.authority,
+        CustomError::InvalidCollectionAuthority
+    );
+    Ok(())
+}
+
+/// Whether `new` is an acceptable replacement for supply cap `current` given
+/// `minted` cards so far. Zero means uncapped.
+fn is_tighter_cap(current: u64, new: u64, minted: u64) -> bool {
+    if new == 0 {
+        return current == 0;
+    }
+    new >= minted && (current == 0 || new <= current)
+}
+
+/// Returns `bps` basis points of `amount`, rounded down.
+fn bps_of(amount: u64, bps: u16) -> Result<u64> {
+    let value = (amount as u128)
+        .checked_mul(bps as u128)
+        .and_then(|v| v.checked_div(MarketplaceConfig::MAX_BPS as u128))
+        .and_then(|v| u64::try_from(v).ok())
+        .ok_or(CustomError::Overflow)?;
+    Ok(value)
+}
+
+/// Where the buyer's side of a sale is paid from.
+enum PaymentSource<'info> {
+    /// Lamports moved out of a signing wallet by the system program
+    Lamports {
+        payer: AccountInfo<'info>,
+        system_program: AccountInfo<'info>,
+    },
+    /// Lamports held in escrow by one of this program's accounts
+    Escrow { account: AccountInfo<'info> },
+    /// SPL tokens moved out of the buyer's token account
+    Token {
+        from: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+        mint: Pubkey,
+    },
+}
+
+impl<'info> PaymentSource<'info> {
+    fn pay(&self, to: &AccountInfo<'info>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        match self {
+            PaymentSource::Lamports {
+                payer,
+                system_program,
+            } => anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    anchor_lang::system_program::Transfer {
+                        from: payer.clone(),
+                        to: to.clone(),
+                    },
+                ),
+                amount,
+            ),
+            PaymentSource::Escrow { account } => {
+                account.sub_lamports(amount)?;
+                to.add_lamports(amount)?;
+                Ok(())
+            }
+            PaymentSource::Token {
+                from,
+                authority,
+                token_program,
+                ..
+            } => token::transfer(
+                CpiContext::new(
+                    token_program.clone(),
+                    token::Transfer {
+                        from: from.clone(),
+                        to: to.clone(),
+                        authority: authority.clone(),
+                    },
+                ),
+                amount,
+            ),
+        }
+    }
+
+    // Creators are paid to their wallet for SOL sales, and to their
+    // associated token account in the payment mint for SPL sales
+    fn is_creator_account(&self, account: &AccountInfo<'info>, creator: &Pubkey) -> bool {
+        match self {
+            PaymentSource::Lamports { .. } | PaymentSource::Escrow { .. } => {
+                account.key() == *creator
+            }
+            PaymentSource::Token { mint, .. } => {
+                account.key() == associated_token::get_associated_token_address(creator, mint)
+            }
+        }
+    }
+}
+
+/// Deserializes an SPL token account passed in without a typed wrapper.
+fn read_token_account(account: &AccountInfo) -> Result<TokenAccount> {
+    require_keys_eq!(*account.owner, token::ID, CustomError::IncorrectOwner);
+    TokenAccount::try_deserialize(&mut &account.data.borrow()[..])
+}
+
+/// Whether the card's listing PDA, passed in without a typed wrapper, holds
+/// an active listing.
+fn is_listed(listing: &AccountInfo, mint: &Pubkey) -> Result<bool> {
+    let (address, _) = Pubkey::find_program_address(&[b"listing", mint.as_ref()], &crate::ID);
+    require_keys_eq!(listing.key(), address, CustomError::InvalidListingAccount);
+    if *listing.owner != crate::ID {
+        return Ok(false);
+    }
+    let listing = ListingData::try_deserialize(&mut &listing.data.borrow()[..])?;
+    Ok(listing.status == ListingStatus::Active)
+}
+
+/// Reads a card's listing PDA passed in without a typed wrapper, creating it
+/// at `payer`'s expense if the card has never been listed.
+fn load_listing<'info>(
+    listing: &AccountInfo<'info>,
+    mint: &Pubkey,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<ListingData> {
+    let (address, bump) =
+        Pubkey::find_program_address(&[b"listing", mint.as_ref()], &crate::ID);
+    require_keys_eq!(listing.key(), address, CustomError::InvalidListingAccount);
+    if *listing.owner == crate::ID {
+        return ListingData::try_deserialize(&mut &listing.data.borrow()[..]);
+    }
+
+    // Fund, allocate and assign separately so lamports already sent to the
+    // address can't block the account from being created
+    let rent = Rent::get()?.minimum_balance(ListingData::LEN);
+    let shortfall = rent.saturating_sub(listing.lamports());
+    if shortfall > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.clone(),
+                    to: listing.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+
+    let bump_seed = [bump];
+    let seeds: &[&[u8]] = &[b"listing", mint.as_ref(), &bump_seed];
+    anchor_lang::system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            anchor_lang::system_program::Allocate {
+                account_to_allocate: listing.clone(),
+            },
+            &[seeds],
+        ),
+        ListingData::LEN as u64,
+    )?;
+    anchor_lang::system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            anchor_lang::system_program::Assign {
+                account_to_assign: listing.clone(),
+            },
+            &[seeds],
+        ),
+        &crate::ID,
+    )?;
+
+    Ok(ListingData {
+        status: ListingStatus::NotActive,
+        seller: Pubkey::default(),
+        mint: *mint,
+        price: 0,
+        payment_mint: None,
+        dutch: None,
+        expires_at: None,
+        reserved_buyer: None,
+        vaulted: false,
+        bounty_deposit: 0,
+        created_at: 0,
+        history: Vec::new(),
+    })
+}
+
+/// Royalty and platform fee taken out of a sale.
+struct SaleProceeds {
+    royalty: u64,
+    platform_fee: u64,
+}
+
+/// Reads a card's metadata account and checks it belongs to `mint`.
+fn load_card_metadata(metadata: &AccountInfo, mint: &Pubkey) -> Result<Metadata> {
+    let metadata = Metadata::try_from(metadata).map_err(|_| CustomError::InvalidMetadata)?;
+    require_keys_eq!(metadata.mint, *mint, CustomError::MetadataMintMismatch);
+    Ok(metadata)
+}
+
+/// Returns the token account passed in for `owner`, checking it is the
+/// owner's associated token account for `mint`. Withdrawals only read from
+/// the treasury's ATA, so funds sent anywhere else would be stranded.
+fn payment_account<'a, 'info>(
+    account: &'a Option<Account<'info, TokenAccount>>,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Result<&'a Account<'info, TokenAccount>> {
+    let account = account.as_ref().ok_or(CustomError::InvalidPaymentAccount)?;
+    require_keys_eq!(
+        account.key(),
+        associated_token::get_associated_token_address(owner, mint),
+        CustomError::InvalidPaymentAccount
+    );
+    require_keys_eq!(account.mint, *mint, CustomError::InvalidPaymentAccount);
+    require_keys_eq!(account.owner, *owner, CustomError::InvalidPaymentAccount);
+    Ok(account)
+}
+
+/// Splits `price` between the card's creators, the treasury and the seller.
+fn settle_sale<'info>(
+    source: &PaymentSource<'info>,
+    metadata: &Metadata,
+    platform_fee_bps: u16,
+    creator_accounts: &[AccountInfo<'info>],
+    treasury: &AccountInfo<'info>,
+    seller: &AccountInfo<'info>,
+    price: u64,
+) -> Result<SaleProceeds> {
+    // Cards minted before a fee increase (or elsewhere) may carry a higher
+    // royalty than the fee leaves room for; cap it so the seller amount
+    // can't underflow
+    let royalty_bps = metadata
+        .seller_fee_basis_points
+        .min(MarketplaceConfig::MAX_BPS.saturating_sub(platform_fee_bps));
+    let royalty = bps_of(price, royalty_bps)?;
+    let platform_fee = bps_of(price, platform_fee_bps)?;
+
+    let creators = metadata.creators.clone().unwrap_or_default();
+    let royalty = pay_creator_royalties(source, &creators, creator_accounts, royalty)?;
+    source.pay(treasury, platform_fee)?;
+
+    let seller_amount = price
+        .checked_sub(royalty)
+        .and_then(|v| v.checked_sub(platform_fee))
+        .ok_or(CustomError::Overflow)?;
+    source.pay(seller, seller_amount)?;
+
+    Ok(SaleProceeds {
+        royalty,
+        platform_fee,
+    })
+}
+
+/// Splits `royalty` pro-rata across the verified creators of a card.
+///
+/// `creator_accounts` must list the verified creators in the same order as
+/// the metadata's creator array. Rounding dust goes to the first verified
+/// creator so the full royalty is always paid out. Returns the amount paid,
+/// which is zero when the card has no verified creators.
+fn pay_creator_royalties<'info>(
+    source: &PaymentSource<'info>,
+    creators: &[Creator],
+    creator_accounts: &[AccountInfo<'info>],
+    royalty: u64,
+) -> Result<u64> {
+    let verified: Vec<&Creator> = creators.iter().filter(|c| c.verified).collect();
+    require!(
+        creator_accounts.len() == verified.len(),
+        CustomError::InvalidCreatorAccounts
+    );
+
+    let shares: Vec<u8> = verified.iter().map(|c| c.share).collect();
+    if royalty == 0 || shares.iter().all(|share| *share == 0) {
+        return Ok(0);
+    }
+    let amounts = royalty_shares(&shares, royalty)?;
+
+    for ((creator, account), amount) in verified.iter().zip(creator_accounts).zip(amounts) {
+        require!(
+            source.is_creator_account(account, &creator.address),
+            CustomError::InvalidCreatorAccounts
+        );
+        require!(account.is_writable, CustomError::InvalidCreatorAccounts);
+
+        source.pay(account, amount)?;
+    }
+
+    Ok(royalty)
+}
+
+/// Splits `royalty` pro-rata by `shares`, giving the rounding dust to the
+/// first share so the amounts always add up to `royalty`.
+fn royalty_shares(shares: &[u8], royalty: u64) -> Result<Vec<u64>> {
+    let total_share: u64 = shares.iter().map(|share| *share as u64).sum();
+
+    let mut amounts = Vec::with_capacity(shares.len());
+    for share in shares {
+        let amount = royalty
+            .checked_mul(*share as u64)
+            .and_then(|v| v.checked_div(total_share))
+            .ok_or(CustomError::Overflow)?;
+        amounts.push(amount);
+    }
+
+    let distributed: u64 = amounts.iter().sum();
+    if let Some(first) = amounts.first_mut() {
+        *first = first
+            .checked_add(royalty - distributed)
+            .ok_or(CustomError::Overflow)?;
+    }
+    Ok(amounts)
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    // Only the program's upgrade authority can bootstrap the config
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::NftProgram>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ CustomError::InvalidConfigAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = MarketplaceConfig::LEN,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut, seeds = [b"treasury"], bump)]
+    pub treasury: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct QueueConfigChange<'info> {
+    // Pays rent for the pending change
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = PendingChange::LEN,
+        seeds = [b"pending_change", config.next_change_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateAdminSet<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ CustomError::InvalidConfigAuthority,
+        constraint = !config.multisig @ CustomError::InvalidAdminSet
+    )]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = AdminSet::LEN,
+        seeds = [b"admin_set"],
+        bump
+    )]
+    pub admin_set: Account<'info, AdminSet>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAdminSet<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut, seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Account<'info, AdminSet>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    #[account(mut)]
+    pub proposer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateProposal<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    #[account(mut, seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Account<'info, AdminSet>,
+
+    #[account(
+        init,
+        payer = proposer,
+        space = Proposal::LEN,
+        seeds = [b"proposal", admin_set.next_proposal_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveProposal<'info> {
+    pub approver: Signer<'info>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Account<'info, AdminSet>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+}
+
+#[derive(Accounts)]
+pub struct ExecutePendingChange<'info> {
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        seeds = [b"pending_change", pending_change.id.to_le_bytes().as_ref()],
+        bump = pending_change.bump
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelPendingChange<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        seeds = [b"pending_change", pending_change.id.to_le_bytes().as_ref()],
+        bump = pending_change.bump
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    // Required without an admin set, and to move metadata update authority
+    pub authority: Option<Signer<'info>>,
+
+    pub new_authority: Signer<'info>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+
+    #[account(address = mpl_token_metadata::ID)]
+    /// CHECK: Metaplex Token Metadata Program
+    pub metadata_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTreasury<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+
+    #[account(mut, seeds = [b"treasury"], bump = config.treasury_bump)]
+    pub treasury: SystemAccount<'info>,
+
+    #[account(mut, address = config.treasury)]
+    pub destination: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTreasuryTokens<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+
+    #[account(seeds = [b"treasury"], bump = config.treasury_bump)]
+    pub treasury: SystemAccount<'info>,
+
+    pub payment_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = treasury,
+    )]
+    pub treasury_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = destination.mint == payment_mint.key() @ CustomError::InvalidPaymentAccount,
+        constraint = destination.owner == config.treasury @ CustomError::InvalidPaymentAccount,
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaymentMint<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut, seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateCollection<'info> {
+    // Pays rent for the registry and the collection NFT
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mut)]
+    pub collection_authority: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = CollectionRegistry::LEN,
+        seeds = [b"collection", collection_mint.key().as_ref()],
+        bump
+    )]
+    pub collection_registry: Account<'info, CollectionRegistry>,
+
+    #[account(
+        init,
+        payer = payer,
+        mint::decimals = 0,
+        mint::authority = collection_authority.key(),
+        mint::freeze_authority = collection_authority.key(),
+    )]
+    pub collection_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = collection_mint,
+        associated_token::authority = payer
+    )]
+    pub collection_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = Metadata::find_pda(&collection_mint.key()).0
+    )]
+    /// CHECK: Metaplex validated
+    pub collection_metadata: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        address = MasterEdition::find_pda(&collection_mint.key()).0
+    )]
+    /// CHECK: Metaplex validated
+    pub collection_master_edition: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        address = CollectionAuthorityRecord::find_pda(
+            &collection_mint.key(),
+            &collection_registry.key()
+        ).0
+    )]
+    /// CHECK: Created by Metaplex when delegating to the registry PDA
+    pub collection_authority_record: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    #[account(address = mpl_token_metadata::ID)]
+    /// CHECK: Master Edition account owned by Token Metadata Program
+    pub metadata_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct MintNFT<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = CardStats::LEN,
+        seeds = [b"stats", mint.key().as_ref()],
+        bump
+    )]
+    pub stats_account: Account<'info, CardStats>,
+
+    #[account(
+        init,
+        payer = payer,
+        mint::decimals = 0,
+        mint::authority = payer,
+        mint::freeze_authority = payer,
+    )]
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        address = Metadata::find_pda(&mint.key()).0
+    )]
+    /// CHECK: Validated by Metaplex
+    pub metadata: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        address = MasterEdition::find_pda(&mint.key()).0
+    )]
+    /// CHECK: Validated by Metaplex
+    pub master_edition: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed, // Use init_if_needed instead of init
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = payer
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    #[account(address = mpl_token_metadata::ID)]
+    /// CHECK: Metaplex Token Metadata Program
+    pub metadata_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    pub collection_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        address = Metadata::find_pda(&collection_mint.key()).0,
+        owner = mpl_token_metadata::ID @ CustomError::IncorrectOwner
+    )]
+    /// CHECK: Checked via constraint
+    pub collection_metadata: UncheckedAccount<'info>,
+
+    #[account(
+        address = MasterEdition::find_pda(&collection_mint.key()).0,
+        owner = mpl_token_metadata::ID @ CustomError::IncorrectOwner
+    )]
+    /// CHECK: Validated by Metaplex constraints
+    pub collection_master_edition: UncheckedAccount<'info>,
+
+    /// Optional: omit for a paid public mint
+    #[account(
+        mut,
+        constraint = collection_authority.key() == collection_registry.authority @ CustomError::InvalidCollectionAuthority
+    )]
+    pub collection_authority: Option<Signer<'info>>,
+
+    #[account(
+        address = CollectionAuthorityRecord::find_pda(
+            &collection_mint.key(),
+            &collection_registry.key()
+        ).0
+    )]
+    /// CHECK: Metaplex delegate record for the registry PDA
+    pub collection_authority_record: UncheckedAccount<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"collection", collection_mint.key().as_ref()],
+        bump = collection_registry.bump,
+        constraint = collection_registry.status == CollectionStatus::Active @ CustomError::CollectionNotActive
+    )]
+    pub collection_registry: Account<'info, CollectionRegistry>,
+
+    // Only used by public mints; co-signed mints leave it out
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = MintCounter::LEN,
+        seeds = [b"mint_counter", collection_mint.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub mint_counter: Option<Account<'info, MintCounter>>,
+}
+
+#[derive(Accounts)]
+pub struct MintNFTAllowlisted<'info> {
+    pub mint_nft: MintNFT<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = mint_nft.payer,
+        space = AllowlistClaim::LEN,
+        seeds = [
+            b"allowlist",
+            mint_nft.collection_mint.key().as_ref(),
+            mint_nft.payer.key().as_ref()
+        ],
+        bump
+    )]
+    pub allowlist_claim: Account<'info, AllowlistClaim>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCollection<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"collection", collection_registry.collection_mint.as_ref()],
+        bump = collection_registry.bump,
+        has_one = authority @ CustomError::InvalidCollectionAuthority
+    )]
+    pub collection_registry: Account<'info, CollectionRegistry>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawCollectionFunds<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+
+    #[account(
+        mut,
+        seeds = [b"collection", collection_registry.collection_mint.as_ref()],
+        bump = collection_registry.bump
+    )]
+    pub collection_registry: Account<'info, CollectionRegistry>,
+
+    #[account(mut)]
+    pub destination: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawCollectionTokens<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+
+    #[account(
+        seeds = [b"collection", collection_registry.collection_mint.as_ref()],
+        bump = collection_registry.bump
+    )]
+    pub collection_registry: Account<'info, CollectionRegistry>,
+
+    pub payment_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = collection_registry,
+    )]
+    pub registry_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = destination.mint == payment_mint.key() @ CustomError::InvalidPaymentAccount,
+    )]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetCollectionStatus<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(seeds = [b"admin_set"], bump = admin_set.bump)]
+    pub admin_set: Option<Account<'info, AdminSet>>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.id.to_le_bytes().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Option<Account<'info, Proposal>>,
+
+    #[account(mut)]
+    pub proposer: Option<SystemAccount<'info>>,
+
+    #[account(
+        mut,
+        seeds = [b"collection", collection_registry.collection_mint.as_ref()],
+        bump = collection_registry.bump
+    )]
+    pub collection_registry: Account<'info, CollectionRegistry>,
+}
+
+#[derive(Accounts)]
+pub struct ListNFT<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = ListingData::LEN,  // Make sure space is consistent
+        seeds = [b"listing", mint.key().as_ref()],
+        bump,
+    )]
+    pub listing: Account<'info, ListingData>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+        constraint = token_account.amount == 1
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ListNFTInVault<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = ListingData::LEN,
+        seeds = [b"listing", mint.key().as_ref()],
+        bump,
+    )]
+    pub listing: Account<'info, ListingData>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+        constraint = token_account.amount == 1 @ CustomError::InvalidNFTOwnership
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        associated_token::mint = mint,
+        associated_token::authority = listing,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = seller,
+        has_one = mint,
+        seeds = [b"listing", mint.key().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, ListingData>,
+
+    pub mint: Account<'info, Mint>,
+
+    // Not needed for vaulted listings
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+        constraint = token_account.amount == 1,
+        constraint = token_account.delegate.is_some() &&
+        token_account.delegate.unwrap() == listing.key(),
+    )]
+    pub token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub token_program: Program<'info, Token>, // Added for delegate operations
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = seller,
+        seeds = [b"listing", mint.key().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, ListingData>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+        constraint = listing.vaulted || (token_account.delegate.is_some() &&
+        token_account.delegate.unwrap() == listing.key()),
+    )]
+    pub token_account: Account<'info, TokenAccount>, // Added token account
+
+    // Required for vaulted listings
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = listing,
+    )]
+    pub vault: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>, // Added for delegate revocation
+    pub system_program: Program<'info, System>,
+}
+#[derive(Accounts)]
+pub struct PurchaseNFT<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    /// CHECK: This is the seller account that will receive the payment
+    #[account(mut, address = listing.seller)]
+    pub seller: SystemAccount<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut, seeds = [b"treasury"], bump = config.treasury_bump)]
+    pub treasury: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"listing", mint.key().as_ref()],
+        bump,
+        
+    )]
+    pub listing: Account<'info, ListingData>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        address = Metadata::find_pda(&mint.key()).0 @ CustomError::MetadataMintMismatch,
+        owner = mpl_token_metadata::ID @ CustomError::IncorrectOwner
+    )]
+    /// CHECK: Deserialized and checked against the listed mint in the handler
+    pub metadata: UncheckedAccount<'info>,
+
+    // Delegated listings move the card from here; vaulted ones from the vault
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+        constraint = seller_token.delegate.is_some() &&
+        seller_token.delegate.unwrap() == listing.key() &&
+        seller_token.delegated_amount >= 1,
+    )]
+    pub seller_token: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = listing,
+    )]
+    pub vault: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = mint,
+        associated_token::authority = buyer,
+    )]
+    pub buyer_token: Account<'info, TokenAccount>,
+
+    // Only required for listings priced in an SPL token; each must be the
+    // owner's associated token account for the payment mint
+    #[account(mut)]
+    pub buyer_payment_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub seller_payment_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub treasury_payment_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireListing<'info> {
+    #[account(
+        mut,
+        seeds = [b"listing", listing.mint.as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, ListingData>,
+
+    // Receives the prune deposit back
+    #[account(mut, address = listing.seller)]
+    pub seller: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PruneListing<'info> {
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"listing", listing.mint.as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, ListingData>,
+
+    #[account(
+        address = associated_token::get_associated_token_address(&listing.seller, &listing.mint)
+    )]
+    /// CHECK: The seller's token account for the listed card; may be closed
+    pub seller_token: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateAuction<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        init,
+        payer = seller,
+        space = Auction::LEN,
+        seeds = [b"auction", mint.key().as_ref()],
+        bump
+    )]
+    pub auction: Account<'info, Auction>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+        constraint = token_account.amount == 1 @ CustomError::InvalidNFTOwnership
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceBid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"auction", auction.mint.as_ref()],
+        bump = auction.bump
+    )]
+    pub auction: Account<'info, Auction>,
+
+    // Required once the auction has a bid, to refund the outbid bidder
+    #[account(mut)]
+    pub previous_bidder: Option<SystemAccount<'info>>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleAuction<'info> {
+    // Anyone can settle; pays for the winner's token account if needed
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mut, address = auction.seller)]
+    pub seller: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = auction.highest_bidder == Some(winner.key()) @ CustomError::AuctionHasNoBids
+    )]
+    pub winner: SystemAccount<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut, seeds = [b"treasury"], bump = config.treasury_bump)]
+    pub treasury: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        has_one = mint,
+        seeds = [b"auction", mint.key().as_ref()],
+        bump = auction.bump,
+        close = seller
+    )]
+    pub auction: Account<'info, Auction>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = ListingData::LEN,
+        seeds = [b"listing", mint.key().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, ListingData>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        address = Metadata::find_pda(&mint.key()).0 @ CustomError::MetadataMintMismatch,
+        owner = mpl_token_metadata::ID @ CustomError::IncorrectOwner
+    )]
+    /// CHECK: Deserialized and checked against the auctioned mint in the handler
+    pub metadata: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+    )]
+    pub seller_token: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = winner,
+    )]
+    pub winner_token: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAuction<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = seller,
+        has_one = mint,
+        seeds = [b"auction", mint.key().as_ref()],
+        bump = auction.bump,
+        close = seller
+    )]
+    pub auction: Account<'info, Auction>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MakeOffer<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
 
     #[account(
         init,
-        payer = payer,
-        space = CardStats::LEN,
-        seeds = [b"stats", mint.key().as_ref()],
+        payer = bidder,
+        space = Offer::LEN,
+        seeds = [b"offer", mint.key().as_ref(), bidder.key().as_ref()],
         bump
     )]
-    pub stats_account: Account<'info, CardStats>,
+    pub offer: Account<'info, Offer>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOffer<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
 
     #[account(
-        init,
-        payer = payer,
-        mint::decimals = 0,
-        mint::authority = payer,
-        mint::freeze_authority = payer,
+        mut,
+        has_one = bidder,
+        seeds = [b"offer", offer.mint.as_ref(), bidder.key().as_ref()],
+        bump = offer.bump,
+        close = bidder
     )]
-    pub mint: Account<'info, Mint>,
+    pub offer: Account<'info, Offer>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOffer<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(mut, address = offer.bidder)]
+    pub bidder: SystemAccount<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
 
+    #[account(mut, seeds = [b"treasury"], bump = config.treasury_bump)]
+    pub treasury: SystemAccount<'info>,
+
+    // Rent goes back to the bidder; the escrowed bid is paid out in the handler
     #[account(
         mut,
-        address = Metadata::find_pda(&mint.key()).0
+        has_one = mint,
+        seeds = [b"offer", mint.key().as_ref(), bidder.key().as_ref()],
+        bump = offer.bump,
+        close = bidder
     )]
-    /// CHECK: Validated by Metaplex
+    pub offer: Account<'info, Offer>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = ListingData::LEN,
+        seeds = [b"listing", mint.key().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, ListingData>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        address = Metadata::find_pda(&mint.key()).0 @ CustomError::MetadataMintMismatch,
+        owner = mpl_token_metadata::ID @ CustomError::IncorrectOwner
+    )]
+    /// CHECK: Deserialized and checked against the offered mint in the handler
     pub metadata: UncheckedAccount<'info>,
 
     #[account(
         mut,
-        address = MasterEdition::find_pda(&mint.key()).0
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+        constraint = seller_token.amount == 1 @ CustomError::NotNFTOwner
     )]
-    /// CHECK: Validated by Metaplex
-    pub master_edition: UncheckedAccount<'info>,
+    pub seller_token: Account<'info, TokenAccount>,
 
     #[account(
-        init_if_needed, // Use init_if_needed instead of init
-        payer = payer,
+        init_if_needed,
+        payer = seller,
         associated_token::mint = mint,
-        associated_token::authority = payer
+        associated_token::authority = bidder,
     )]
-    pub token_account: Account<'info, TokenAccount>,
+    pub bidder_token: Account<'info, TokenAccount>,
 
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
-
-    #[account(address = mpl_token_metadata::ID)]
-    /// CHECK: Metaplex Token Metadata Program
-    pub metadata_program: UncheckedAccount<'info>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-    pub collection_mint: Account<'info, Mint>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct MakeCollectionOffer<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
 
     #[account(
-        mut,
-        address = Metadata::find_pda(&collection_mint.key()).0,
-        owner = mpl_token_metadata::ID @ CustomError::IncorrectOwner
+        init,
+        payer = bidder,
+        space = CollectionOffer::LEN,
+        seeds = [
+            b"collection_offer",
+            collection_registry.collection_mint.as_ref(),
+            bidder.key().as_ref(),
+            id.to_le_bytes().as_ref()
+        ],
+        bump
     )]
-    /// CHECK: Checked via constraint
-    pub collection_metadata: UncheckedAccount<'info>,
+    pub offer: Account<'info, CollectionOffer>,
 
     #[account(
-        address = MasterEdition::find_pda(&collection_mint.key()).0,
-        owner = mpl_token_metadata::ID @ CustomError::IncorrectOwner
+        seeds = [b"collection", collection_registry.collection_mint.as_ref()],
+        bump = collection_registry.bump
     )]
-    /// CHECK: Validated by Metaplex constraints
-    pub collection_master_edition: UncheckedAccount<'info>,
+    pub collection_registry: Account<'info, CollectionRegistry>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelCollectionOffer<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
 
-    /// CHECK: Collection authority account verified against COLLECTION_AUTHORITY constant
     #[account(
         mut,
-        constraint = collection_authority.key() == COLLECTION_AUTHORITY @ CustomError::InvalidCollectionAuthority
+        has_one = bidder,
+        seeds = [
+            b"collection_offer",
+            offer.collection_mint.as_ref(),
+            bidder.key().as_ref(),
+            offer.id.to_le_bytes().as_ref()
+        ],
+        bump = offer.bump,
+        close = bidder
     )]
-    pub collection_authority: Signer<'info>,
+    pub offer: Account<'info, CollectionOffer>,
 }
 
 #[derive(Accounts)]
-pub struct ListNFT<'info> {
+pub struct AcceptCollectionOffer<'info> {
     #[account(mut)]
     pub seller: Signer<'info>,
 
+    #[account(mut, address = offer.bidder)]
+    pub bidder: SystemAccount<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut, seeds = [b"treasury"], bump = config.treasury_bump)]
+    pub treasury: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"collection_offer",
+            offer.collection_mint.as_ref(),
+            bidder.key().as_ref(),
+            offer.id.to_le_bytes().as_ref()
+        ],
+        bump = offer.bump,
+        close = bidder
+    )]
+    pub offer: Account<'info, CollectionOffer>,
+
     #[account(
         init_if_needed,
         payer = seller,
-        space = ListingData::LEN,  // Make sure space is consistent
+        space = ListingData::LEN,
         seeds = [b"listing", mint.key().as_ref()],
-        bump,
+        bump
     )]
     pub listing: Account<'info, ListingData>,
 
     pub mint: Account<'info, Mint>,
 
+    #[account(
+        address = Metadata::find_pda(&mint.key()).0 @ CustomError::MetadataMintMismatch,
+        owner = mpl_token_metadata::ID @ CustomError::IncorrectOwner
+    )]
+    /// CHECK: Deserialized and checked against the offer's collection in the handler
+    pub metadata: UncheckedAccount<'info>,
+
+    #[account(seeds = [b"stats", mint.key().as_ref()], bump)]
+    pub stats: Account<'info, CardStats>,
+
     #[account(
         mut,
         associated_token::mint = mint,
         associated_token::authority = seller,
-        constraint = token_account.amount == 1
+        constraint = seller_token.amount == 1 @ CustomError::NotNFTOwner
     )]
-    pub token_account: Account<'info, TokenAccount>,
+    pub seller_token: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = seller,
+        associated_token::mint = mint,
+        associated_token::authority = bidder,
+    )]
+    pub bidder_token: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct CreateBundleListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    #[account(
+        init,
+        payer = seller,
+        space = BundleListing::LEN,
+        seeds = [b"bundle", seller.key().as_ref(), id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub bundle: Account<'info, BundleListing>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
 
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PurchaseBundle<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(mut, address = bundle.seller)]
+    pub seller: SystemAccount<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    #[account(mut, seeds = [b"treasury"], bump = config.treasury_bump)]
+    pub treasury: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"bundle", bundle.seller.as_ref(), bundle.id.to_le_bytes().as_ref()],
+        bump = bundle.bump,
+        close = seller
+    )]
+    pub bundle: Account<'info, BundleListing>,
 
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateListing<'info> {
+pub struct CancelBundleListing<'info> {
     #[account(mut)]
     pub seller: Signer<'info>,
 
-    #[account(
-        mut,
-        has_one = seller,
-        has_one = mint,
-        seeds = [b"listing", mint.key().as_ref()],
-        bump
-    )]
-    pub listing: Account<'info, ListingData>,
+    #[account(
+        mut,
+        has_one = seller,
+        seeds = [b"bundle", seller.key().as_ref(), bundle.id.to_le_bytes().as_ref()],
+        bump = bundle.bump,
+        close = seller
+    )]
+    pub bundle: Account<'info, BundleListing>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct ProposeSwap<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = proposer,
+        space = Swap::LEN,
+        seeds = [b"swap", proposer.key().as_ref(), id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub swap: Account<'info, Swap>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptSwap<'info> {
+    #[account(mut, address = swap.counterparty @ CustomError::InvalidSwapCounterparty)]
+    pub counterparty: Signer<'info>,
+
+    #[account(mut, address = swap.proposer)]
+    pub proposer: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"swap", swap.proposer.as_ref(), swap.id.to_le_bytes().as_ref()],
+        bump = swap.bump,
+        close = proposer
+    )]
+    pub swap: Account<'info, Swap>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, MarketplaceConfig>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSwap<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = proposer,
+        seeds = [b"swap", proposer.key().as_ref(), swap.id.to_le_bytes().as_ref()],
+        bump = swap.bump,
+        close = proposer
+    )]
+    pub swap: Account<'info, Swap>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[account]
+pub struct MarketplaceConfig {
+    pub authority: Pubkey,
+    pub treasury: Pubkey,
+    pub royalty_bps: u16,
+    pub platform_fee_bps: u16,
+    pub paused: bool,
+    pub mint_paused: bool,
+    pub list_paused: bool,
+    pub purchase_paused: bool,
+    pub pending_authority: Option<Pubkey>,
+    pub timelock_delay: i64,
+    pub next_change_id: u64,
+    pub multisig: bool,
+    pub payment_mints: Vec<Pubkey>,
+    // Deposit sellers post with each delegated listing, paid to whoever
+    // prunes it if it goes stale
+    pub prune_bounty: u64,
+    pub bump: u8,
+    pub treasury_bump: u8,
+}
+
+impl MarketplaceConfig {
+    pub const MAX_PAYMENT_MINTS: usize = 5;
+    pub const LEN: usize = 8 + 32 + 32 + 2 + 2 + 1 + 1 + 1 + 1 + (1 + 32) + 8 + 8 + 1
+        + (4 + Self::MAX_PAYMENT_MINTS * 32) + 8 + 1 + 1;
+    pub const MAX_BPS: u16 = 10_000;
+    pub const MAX_PLATFORM_FEE_BPS: u16 = 1_000;
+    pub const MAX_TIMELOCK_DELAY: i64 = 30 * 24 * 60 * 60;
+    pub const MAX_PRUNE_BOUNTY: u64 = 10_000_000;
+
+    // Authority changes only nominate the new key; it still has to sign
+    // accept_authority before taking over.
+    pub fn apply(&mut self, change: &ConfigChange) {
+        match change {
+            ConfigChange::Treasury(treasury) => self.treasury = *treasury,
+            ConfigChange::RoyaltyBps(bps) => self.royalty_bps = *bps,
+            ConfigChange::PlatformFeeBps(bps) => self.platform_fee_bps = *bps,
+            ConfigChange::Authority(authority) => self.pending_authority = Some(*authority),
+            ConfigChange::TimelockDelay(delay) => self.timelock_delay = *delay,
+            ConfigChange::PruneBounty(bounty) => self.prune_bounty = *bounty,
+        }
+    }
+
+    // Royalty and platform fee come out of the same price, so together they
+    // can never exceed it
+    pub fn validate_fees(royalty_bps: u16, platform_fee_bps: u16) -> Result<()> {
+        require!(
+            platform_fee_bps <= Self::MAX_PLATFORM_FEE_BPS,
+            CustomError::InvalidPlatformFeeBps
+        );
+        require!(
+            royalty_bps <= Self::MAX_BPS - platform_fee_bps,
+            CustomError::InvalidRoyaltyBps
+        );
+        Ok(())
+    }
+
+    pub fn accepts_payment_mint(&self, mint: &Pubkey) -> bool {
+        self.payment_mints.contains(mint)
+    }
+
+    // The global flag pauses every group; cancel_listing is never paused
+    pub fn is_paused(&self, scope: PauseScope) -> bool {
+        self.paused
+            || match scope {
+                PauseScope::All => false,
+                PauseScope::Mint => self.mint_paused,
+                PauseScope::List => self.list_paused,
+                PauseScope::Purchase => self.purchase_paused,
+            }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ConfigChange {
+    Treasury(Pubkey),
+    RoyaltyBps(u16),
+    PlatformFeeBps(u16),
+    Authority(Pubkey),
+    TimelockDelay(i64),
+    PruneBounty(u64),
+}
+
+impl ConfigChange {
+    pub const LEN: usize = 1 + 32;
+
+    pub fn validate(&self, config: &MarketplaceConfig) -> Result<()> {
+        match self {
+            ConfigChange::Treasury(_) => {}
+            ConfigChange::RoyaltyBps(bps) => {
+                MarketplaceConfig::validate_fees(*bps, config.platform_fee_bps)?;
+            }
+            ConfigChange::PlatformFeeBps(bps) => {
+                MarketplaceConfig::validate_fees(config.royalty_bps, *bps)?;
+            }
+            ConfigChange::Authority(authority) => {
+                require!(
+                    *authority != Pubkey::default() && *authority != config.authority,
+                    CustomError::InvalidNewAuthority
+                );
+            }
+            ConfigChange::TimelockDelay(delay) => {
+                require!(
+                    (0..=MarketplaceConfig::MAX_TIMELOCK_DELAY).contains(delay),
+                    CustomError::InvalidTimelockDelay
+                );
+            }
+            ConfigChange::PruneBounty(bounty) => {
+                require!(
+                    *bounty <= MarketplaceConfig::MAX_PRUNE_BOUNTY,
+                    CustomError::InvalidPruneBounty
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[account]
+pub struct PendingChange {
+    pub id: u64,
+    pub payer: Pubkey,
+    pub change: ConfigChange,
+    pub effective_at: i64,
+    pub bump: u8,
+}
+
+impl PendingChange {
+    pub const LEN: usize = 8 + 8 + 32 + ConfigChange::LEN + 8 + 1;
+
+    /// When a change queued at `now` under `delay` may be executed.
+    pub fn effective_at(now: i64, delay: i64) -> Result<i64> {
+        Ok(now.checked_add(delay).ok_or(CustomError::Overflow)?)
+    }
+
+    pub fn is_ready(&self, now: i64) -> bool {
+        now >= self.effective_at
+    }
+}
+
+#[account]
+pub struct AdminSet {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub next_proposal_id: u64,
+    pub bump: u8,
+}
+
+impl AdminSet {
+    pub const MAX_SIGNERS: usize = 10;
+    pub const LEN: usize = 8 + (4 + Self::MAX_SIGNERS * 32) + 1 + 8 + 1;
+
+    pub fn validate(signers: &[Pubkey], threshold: u8) -> Result<()> {
+        require!(
+            !signers.is_empty() && signers.len() <= Self::MAX_SIGNERS,
+            CustomError::InvalidAdminSet
+        );
+        require!(
+            threshold > 0 && threshold as usize <= signers.len(),
+            CustomError::InvalidAdminSet
+        );
+        for (i, signer) in signers.iter().enumerate() {
+            require!(
+                !signers[..i].contains(signer),
+                CustomError::InvalidAdminSet
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminAction {
+    ConfigChange(ConfigChange),
+    CreateCollection {
+        collection_mint: Pubkey,
+        collection_authority: Pubkey,
+    },
+    WithdrawTreasury {
+        amount: u64,
+    },
+    UpdateAdminSet {
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    },
+    SetPaymentMint {
+        mint: Pubkey,
+        accepted: bool,
+    },
+    WithdrawTreasuryTokens {
+        mint: Pubkey,
+        amount: u64,
+    },
+    CancelPendingChange {
+        id: u64,
+    },
+    SetPaused {
+        scope: PauseScope,
+        paused: bool,
+    },
+    SetCollectionStatus {
+        collection_mint: Pubkey,
+        status: CollectionStatus,
+    },
+    WithdrawCollectionFunds {
+        collection_mint: Pubkey,
+        amount: u64,
+    },
+    WithdrawCollectionTokens {
+        collection_mint: Pubkey,
+        mint: Pubkey,
+        amount: u64,
+    },
+    AcceptAuthority {
+        new_authority: Pubkey,
+    },
+}
+
+impl AdminAction {
+    // Largest variant is UpdateAdminSet
+    pub const LEN: usize = 1 + (4 + AdminSet::MAX_SIGNERS * 32) + 1;
+}
 
-    pub mint: Account<'info, Mint>,
+#[account]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub action: AdminAction,
+    pub approvals: Vec<Pubkey>,
+    pub executed: bool,
+    pub bump: u8,
+}
 
-    #[account(
-        mut,
-        associated_token::mint = mint,
-        associated_token::authority = seller,
-        constraint = token_account.amount == 1,
-        constraint = token_account.delegate.is_some() &&
-        token_account.delegate.unwrap() == listing.key(),
-    )]
-    pub token_account: Account<'info, TokenAccount>,
+impl Proposal {
+    pub const LEN: usize =
+        8 + 8 + 32 + AdminAction::LEN + (4 + AdminSet::MAX_SIGNERS * 32) + 1 + 1;
+}
 
-    pub token_program: Program<'info, Token>, // Added for delegate operations
-    pub system_program: Program<'info, System>,
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseScope {
+    All,
+    Mint,
+    List,
+    Purchase,
 }
 
-#[derive(Accounts)]
-pub struct CancelListing<'info> {
-    #[account(mut)]
-    pub seller: Signer<'info>,
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollectionStatus {
+    Active,
+    Inactive,
+}
 
-    #[account(
-        mut,
-        has_one = seller,
-        seeds = [b"listing", mint.key().as_ref()],
-        bump
-    )]
-    pub listing: Account<'info, ListingData>,
+#[account]
+pub struct CollectionRegistry {
+    pub collection_mint: Pubkey,
+    pub authority: Pubkey,
+    pub royalty_bps: u16,
+    pub minted: u64,
+    pub mint_price: u64,
+    pub allowlist_root: Option<[u8; 32]>,
+    pub phases: Vec<MintPhase>,
+    // Bumped whenever the phases are replaced
+    pub phases_version: u32,
+    pub max_per_wallet: u32,
+    pub max_supply: u64,
+    pub rarity_caps: [u32; RarityType::COUNT],
+    pub rarity_minted: [u32; RarityType::COUNT],
+    pub status: CollectionStatus,
+    pub bump: u8,
+}
 
-    pub mint: Account<'info, Mint>,
+impl CollectionRegistry {
+    pub const MAX_PHASES: usize = 4;
+    pub const LEN: usize = 8
+        + 32
+        + 32
+        + 2
+        + 8
+        + 8
+        + (1 + 32)
+        + (4 + Self::MAX_PHASES * MintPhase::LEN)
+        + 4
+        + 4
+        + 8
+        + (4 * RarityType::COUNT)
+        + (4 * RarityType::COUNT)
+        + 1
+        + 1;
 
-    #[account(
-        mut,
-        associated_token::mint = mint,
-        associated_token::authority = seller,
-        constraint = token_account.delegate.is_some() &&
-        token_account.delegate.unwrap() == listing.key(),
-    )]
-    pub token_account: Account<'info, TokenAccount>, // Added token account
+    // Terms of a mint without the authority co-signature. Collections with no
+    // phases fall back to the flat mint price; otherwise the phase open at
+    // `now` must allow the requested eligibility.
+    fn public_mint_terms(&self, eligibility: MintEligibility, now: i64) -> Result<PublicMintTerms> {
+        if self.phases.is_empty() {
+            if eligibility == MintEligibility::Public {
+                require!(self.mint_price > 0, CustomError::PublicMintDisabled);
+            }
+            return Ok(PublicMintTerms {
+                price: self.mint_price,
+                phase: None,
+            });
+        }
 
-    pub token_program: Program<'info, Token>, // Added for delegate revocation
-    pub system_program: Program<'info, System>,
+        let (index, phase) = self
+            .phases
+            .iter()
+            .enumerate()
+            .find(|(_, phase)| phase.start_at <= now && now < phase.end_at)
+            .ok_or(CustomError::MintPhaseNotActive)?;
+        require!(
+            phase.eligibility == eligibility,
+            CustomError::MintPhaseNotActive
+        );
+
+        Ok(PublicMintTerms {
+            price: phase.price,
+            phase: Some(index),
+        })
+    }
 }
-#[derive(Accounts)]
-pub struct PurchaseNFT<'info> {
-    #[account(mut)]
-    pub buyer: Signer<'info>,
 
-    /// CHECK: This is the seller account that will receive the payment
-    #[account(mut)]
-    pub seller: SystemAccount<'info>,
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MintEligibility {
+    Public,
+    Allowlist,
+}
 
-    /// CHECK: Collection authority that receives royalties
-    #[account(
-        mut,
-        constraint = collection_authority.key() == COLLECTION_AUTHORITY @ CustomError::InvalidCollectionAuthority
-    )]
-    pub collection_authority: AccountInfo<'info>,
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct MintPhase {
+    pub start_at: i64,
+    pub end_at: i64,
+    pub price: u64,
+    pub eligibility: MintEligibility,
+    pub max_per_wallet: u32,
+}
 
-    #[account(
-        mut,
-        seeds = [b"listing", mint.key().as_ref()],
-        bump,
-        
-    )]
-    pub listing: Account<'info, ListingData>,
+impl MintPhase {
+    pub const LEN: usize = 8 + 8 + 8 + 1 + 4;
+}
 
-    pub mint: Account<'info, Mint>,
+// Per-wallet tally of self-served mints in a collection. Phase counts are
+// indexed by position in `CollectionRegistry::phases`.
+#[account]
+pub struct MintCounter {
+    pub minted: u32,
+    pub phase_minted: [u32; CollectionRegistry::MAX_PHASES],
+    pub phases_version: u32,
+    pub bump: u8,
+}
 
-    #[account(
-        mut,
-        associated_token::mint = mint,
-        associated_token::authority = seller,
-        constraint = seller_token.delegate.is_some() &&
-        seller_token.delegate.unwrap() == listing.key() &&
-        seller_token.delegated_amount >= 1,
-    )]
-    pub seller_token: Account<'info, TokenAccount>,
+impl MintCounter {
+    pub const LEN: usize = 8 + 4 + (4 * CollectionRegistry::MAX_PHASES) + 4 + 1;
+}
 
-    #[account(
-        init_if_needed,
-        payer = buyer,
-        associated_token::mint = mint,
-        associated_token::authority = buyer,
-    )]
-    pub buyer_token: Account<'info, TokenAccount>,
+#[account]
+pub struct AllowlistClaim {
+    pub minted: u32,
+    pub bump: u8,
+}
 
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>,
+impl AllowlistClaim {
+    pub const LEN: usize = 8 + 4 + 1;
 }
 
 #[account]
@@ -808,13 +4872,157 @@ pub struct ListingData {
     pub seller: Pubkey,
     pub mint: Pubkey,
     pub price: u64,
+    // None for listings priced in lamports
+    pub payment_mint: Option<Pubkey>,
+    pub dutch: Option<DutchPricing>,
+    pub expires_at: Option<i64>,
+    // Only this wallet may buy when set
+    pub reserved_buyer: Option<Pubkey>,
+    // Set while the card sits in the listing's vault
+    pub vaulted: bool,
+    // Posted by the seller of a delegated listing; paid to whoever prunes
+    // it once stale, otherwise returned when the listing closes
+    pub bounty_deposit: u64,
     pub created_at: i64,
     pub history: Vec<TradeHistory>,
 }
 
 impl ListingData {
     pub const MAX_HISTORY: usize = 16;
-    pub const LEN: usize = 32 + 32 + 8 + 8 + 4 + (Self::MAX_HISTORY * TradeHistory::LEN);
+    pub const LEN: usize = 8 + 1 + 32 + 32 + 8 + (1 + 32) + (1 + DutchPricing::LEN) + (1 + 8)
+        + (1 + 32) + 1 + 8 + 8 + 4 + (Self::MAX_HISTORY * TradeHistory::LEN);
+
+    pub fn ensure_reusable(&self) -> Result<()> {
+        require!(
+            self.history.is_empty() || self.status == ListingStatus::NotActive,
+            CustomError::ListingAlreadyActive
+        );
+        require!(!self.vaulted, CustomError::ListingVaultNotEmpty);
+        Ok(())
+    }
+
+    fn open(&mut self, seller: Pubkey, mint: Pubkey, terms: ListingTerms, vaulted: bool, now: i64) {
+        self.status = ListingStatus::Active;
+        self.seller = seller;
+        self.mint = mint;
+        self.price = terms.price;
+        self.payment_mint = terms.payment_mint;
+        self.dutch = terms.dutch;
+        self.expires_at = terms.expires_at;
+        self.reserved_buyer = terms.reserved_buyer;
+        self.vaulted = vaulted;
+        self.created_at = now;
+
+        self.record(TradeHistory {
+            price: terms.price,
+            timestamp: now,
+            action: TradeAction::List,
+            royalty: 0,
+            platform_fee: 0,
+        });
+    }
+
+    pub fn validate_expiry(expires_at: Option<i64>, now: i64) -> Result<()> {
+        if let Some(expires_at) = expires_at {
+            require!(expires_at > now, CustomError::InvalidListingExpiry);
+        }
+        Ok(())
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        matches!(self.expires_at, Some(expires_at) if now >= expires_at)
+    }
+
+    pub fn current_price(&self, now: i64) -> Result<u64> {
+        match &self.dutch {
+            Some(dutch) => dutch.price_at(self.price, now.saturating_sub(self.created_at)),
+            None => Ok(self.price),
+        }
+    }
+
+    // Pays out the seller's deposit, which sits on top of the listing's rent.
+    // Sales outside the listing pay it to the card's holder, who is the
+    // seller unless the listing had already gone stale.
+    fn release_deposit<'info>(
+        &mut self,
+        listing: &AccountInfo<'info>,
+        to: &AccountInfo<'info>,
+    ) -> Result<()> {
+        let deposit = std::mem::take(&mut self.bounty_deposit);
+        if deposit > 0 {
+            listing.sub_lamports(deposit)?;
+            to.add_lamports(deposit)?;
+        }
+        Ok(())
+    }
+
+    // For cards sold or swapped outside the listing: a fixed-price listing
+    // can't be filled once its card has changed hands, so it is closed out,
+    // the deposit goes back to the previous holder and the trade is recorded
+    // under the new owner.
+    fn hand_over<'info>(
+        &mut self,
+        listing: &AccountInfo<'info>,
+        holder: &AccountInfo<'info>,
+        new_owner: Pubkey,
+        mint: Pubkey,
+        entry: TradeHistory,
+    ) -> Result<()> {
+        self.release_deposit(listing, holder)?;
+        self.status = ListingStatus::NotActive;
+        self.seller = new_owner;
+        self.mint = mint;
+        self.record(entry);
+        Ok(())
+    }
+
+    // Keeps the most recent entries once the history is full
+    pub fn record(&mut self, entry: TradeHistory) {
+        if self.history.len() >= Self::MAX_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push(entry);
+    }
+}
+
+/// Descending price schedule for a Dutch listing, starting from the listing price.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DutchPricing {
+    pub floor_price: u64,
+    pub duration: i64,
+    // Zero decays linearly; otherwise the price drops once per interval
+    pub step_interval: i64,
+}
+
+impl DutchPricing {
+    pub const LEN: usize = 8 + 8 + 8;
+
+    pub fn validate(&self, start_price: u64) -> Result<()> {
+        require!(
+            self.floor_price > 0 && self.floor_price < start_price,
+            CustomError::InvalidDutchPricing
+        );
+        require!(
+            self.duration > 0 && (0..=self.duration).contains(&self.step_interval),
+            CustomError::InvalidDutchPricing
+        );
+        Ok(())
+    }
+
+    pub fn price_at(&self, start_price: u64, elapsed: i64) -> Result<u64> {
+        if elapsed >= self.duration {
+            return Ok(self.floor_price);
+        }
+
+        let mut elapsed = elapsed.max(0);
+        if self.step_interval > 0 {
+            elapsed -= elapsed % self.step_interval;
+        }
+
+        let decay = (start_price - self.floor_price) as u128 * elapsed as u128
+            / self.duration as u128;
+        Ok(start_price - decay as u64)
+    }
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
@@ -822,10 +5030,12 @@ pub struct TradeHistory {
     pub price: u64,
     pub timestamp: i64,
     pub action: TradeAction,
+    pub royalty: u64,
+    pub platform_fee: u64,
 }
 
 impl TradeHistory {
-    pub const LEN: usize = 8 + 8 + 1;
+    pub const LEN: usize = 8 + 8 + 1 + 8 + 8;
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
@@ -834,6 +5044,130 @@ pub enum TradeAction {
     UpdatePrice,
     Purchase,
     Cancel,
+    AuctionSettled,
+    OfferAccepted,
+    Expired,
+    Invalidated,
+    Swapped,
+}
+
+#[account]
+pub struct Auction {
+    pub seller: Pubkey,
+    pub mint: Pubkey,
+    pub reserve_price: u64,
+    pub min_increment_bps: u16,
+    pub highest_bid: u64,
+    pub highest_bidder: Option<Pubkey>,
+    pub end_at: i64,
+    pub bump: u8,
+}
+
+impl Auction {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 2 + 8 + (1 + 32) + 8 + 1;
+    pub const MAX_DURATION: i64 = 30 * 24 * 60 * 60;
+
+    // The first bid must meet the reserve; later bids must beat the current
+    // one by at least the minimum increment (and always by one lamport)
+    pub fn min_next_bid(&self) -> Result<u64> {
+        if self.highest_bidder.is_none() {
+            return Ok(self.reserve_price);
+        }
+        let increment = bps_of(self.highest_bid, self.min_increment_bps)?.max(1);
+        Ok(self
+            .highest_bid
+            .checked_add(increment)
+            .ok_or(CustomError::Overflow)?)
+    }
+}
+
+#[account]
+pub struct Offer {
+    pub bidder: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl Offer {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 1;
+}
+
+#[account]
+pub struct CollectionOffer {
+    pub id: u64,
+    pub bidder: Pubkey,
+    pub collection_mint: Pubkey,
+    pub amount: u64,
+    pub filter: CardFilter,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl CollectionOffer {
+    pub const LEN: usize = 8 + 8 + 32 + 32 + 8 + CardFilter::LEN + 8 + 1;
+}
+
+/// Optional trait requirements a card must meet to fill a collection offer.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct CardFilter {
+    pub element: Option<ElementType>,
+    pub rarity: Option<RarityType>,
+    pub min_attack: Option<u8>,
+    pub min_defense: Option<u8>,
+}
+
+impl CardFilter {
+    pub const LEN: usize = (1 + 1) + (1 + 1) + (1 + 1) + (1 + 1);
+
+    pub fn matches(&self, stats: &CardStats) -> bool {
+        !matches!(&self.element, Some(e) if *e != stats.element)
+            && !matches!(&self.rarity, Some(r) if *r != stats.rarity)
+            && !matches!(self.min_attack, Some(a) if stats.attack < a)
+            && !matches!(self.min_defense, Some(d) if stats.defense < d)
+    }
+}
+
+#[account]
+pub struct BundleListing {
+    pub id: u64,
+    pub seller: Pubkey,
+    pub mints: Vec<Pubkey>,
+    pub price: u64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl BundleListing {
+    pub const MAX_CARDS: usize = 5;
+    pub const LEN: usize = 8 + 8 + 32 + (4 + Self::MAX_CARDS * 32) + 8 + 8 + 1;
+}
+
+#[account]
+pub struct Swap {
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub counterparty: Pubkey,
+    pub offered_mints: Vec<Pubkey>,
+    pub requested_mints: Vec<Pubkey>,
+    pub proposer_sweetener: u64,
+    pub counterparty_sweetener: u64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl Swap {
+    pub const MAX_CARDS_PER_SIDE: usize = 3;
+    pub const LEN: usize = 8
+        + 8
+        + 32
+        + 32
+        + 2 * (4 + Self::MAX_CARDS_PER_SIDE * 32)
+        + 8
+        + 8
+        + 8
+        + 1;
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Default)]
@@ -856,6 +5190,14 @@ pub enum RarityType {
     GodTier,
 }
 
+impl RarityType {
+    pub const COUNT: usize = 6;
+
+    pub fn index(&self) -> usize {
+        self.clone() as usize
+    }
+}
+
 #[error_code]
 pub enum CustomError {
     #[msg("Incorrect Owner")]
@@ -884,4 +5226,343 @@ pub enum CustomError {
     ListingNotActive,
     #[msg("Not Owner")]
     NotNFTOwner,
+    #[msg("Invalid config authority")]
+    InvalidConfigAuthority,
+    #[msg("Invalid royalty basis points (royalty plus platform fee max 10000)")]
+    InvalidRoyaltyBps,
+    #[msg("Invalid token metadata account")]
+    InvalidMetadata,
+    #[msg("Metadata does not belong to the listed mint")]
+    MetadataMintMismatch,
+    #[msg("Creator accounts do not match the verified creators in metadata")]
+    InvalidCreatorAccounts,
+    #[msg("Invalid platform fee basis points (max 1000)")]
+    InvalidPlatformFeeBps,
+    #[msg("Program is paused")]
+    ProgramPaused,
+    #[msg("Invalid or unexpected new authority")]
+    InvalidNewAuthority,
+    #[msg("Collection is not registered as active")]
+    CollectionNotActive,
+    #[msg("Public minting is not enabled for this collection")]
+    PublicMintDisabled,
+    #[msg("Allowlist minting is not enabled for this collection")]
+    AllowlistNotEnabled,
+    #[msg("Invalid allowlist proof")]
+    InvalidAllowlistProof,
+    #[msg("Allowlist allowance exceeded")]
+    AllowlistAllowanceExceeded,
+    #[msg("Mint phases must be ordered and non-overlapping")]
+    InvalidMintPhases,
+    #[msg("No mint phase open for this kind of mint")]
+    MintPhaseNotActive,
+    #[msg("Wallet mint limit exceeded")]
+    MintLimitExceeded,
+    #[msg("Collection max supply reached")]
+    MaxSupplyReached,
+    #[msg("Supply cap reached for this rarity")]
+    RaritySupplyReached,
+    #[msg("Supply caps can only be lowered and not below the minted count")]
+    InvalidSupplyCap,
+    #[msg("Invalid timelock delay")]
+    InvalidTimelockDelay,
+    #[msg("Timelock has not elapsed yet")]
+    TimelockNotElapsed,
+    #[msg("Invalid admin set")]
+    InvalidAdminSet,
+    #[msg("Signer is not in the admin set")]
+    NotAdminSigner,
+    #[msg("An approved proposal is required")]
+    ProposalRequired,
+    #[msg("Proposal does not match this action")]
+    ProposalActionMismatch,
+    #[msg("Proposal has already been executed")]
+    ProposalAlreadyExecuted,
+    #[msg("Proposal has not reached the approval threshold")]
+    ProposalNotApproved,
+    #[msg("Signer has already approved this proposal")]
+    AlreadyApproved,
+    #[msg("Payment mint is not accepted by the marketplace")]
+    PaymentMintNotAccepted,
+    #[msg("Too many accepted payment mints")]
+    TooManyPaymentMints,
+    #[msg("Missing or invalid payment token account")]
+    InvalidPaymentAccount,
+    #[msg("Auction end time must be in the future and within 30 days")]
+    InvalidAuctionEnd,
+    #[msg("Invalid minimum bid increment")]
+    InvalidBidIncrement,
+    #[msg("Auction has ended")]
+    AuctionEnded,
+    #[msg("Auction has not ended yet")]
+    AuctionNotEnded,
+    #[msg("Bid is below the minimum next bid")]
+    BidTooLow,
+    #[msg("Seller cannot bid on their own auction")]
+    SellerCannotBid,
+    #[msg("Missing or invalid previous bidder account")]
+    InvalidPreviousBidder,
+    #[msg("Auction already has bids")]
+    AuctionHasBids,
+    #[msg("Auction has no winning bid")]
+    AuctionHasNoBids,
+    #[msg("Invalid Dutch pricing schedule")]
+    InvalidDutchPricing,
+    #[msg("Dutch listings cannot be repriced")]
+    DutchListingNotUpdatable,
+    #[msg("Card is not a verified member of the offer's collection")]
+    CardNotInCollection,
+    #[msg("Card does not match the offer's filters")]
+    CardFilterMismatch,
+    #[msg("Listing expiry must be in the future")]
+    InvalidListingExpiry,
+    #[msg("Listing has expired")]
+    ListingExpired,
+    #[msg("Listing has not expired")]
+    ListingNotExpired,
+    #[msg("Listing is reserved for another buyer")]
+    ListingReserved,
+    #[msg("Bundles must contain between 2 and 5 cards")]
+    InvalidBundleSize,
+    #[msg("Invalid or out of order bundle card accounts")]
+    InvalidBundleAccounts,
+    #[msg("Each side of a swap must have between 1 and 3 cards")]
+    InvalidSwapSize,
+    #[msg("Invalid or out of order swap card accounts")]
+    InvalidSwapAccounts,
+    #[msg("Invalid swap counterparty")]
+    InvalidSwapCounterparty,
+    #[msg("Listing vault account is missing")]
+    MissingListingVault,
+    #[msg("Listing vault still holds the card; cancel the listing first")]
+    ListingVaultNotEmpty,
+    #[msg("Listing is still backed by the seller's token account")]
+    ListingNotStale,
+    #[msg("Prune bounty is too large (max 0.01 SOL)")]
+    InvalidPruneBounty,
+    #[msg("Public mints need the payer's mint counter account")]
+    MissingMintCounter,
+    #[msg("Listing account does not match the card")]
+    InvalidListingAccount,
+    #[msg("Proposer account does not match the proposal")]
+    InvalidProposer,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn royalty_shares_split_pro_rata() {
+        assert_eq!(royalty_shares(&[100], 30_000).unwrap(), vec![30_000]);
+        assert_eq!(royalty_shares(&[70, 30], 1_000).unwrap(), vec![700, 300]);
+    }
+
+    #[test]
+    fn royalty_shares_give_dust_to_first_creator() {
+        assert_eq!(royalty_shares(&[34, 33, 33], 100).unwrap(), vec![34, 33, 33]);
+        assert_eq!(royalty_shares(&[33, 33, 34], 10).unwrap(), vec![4, 3, 3]);
+        assert_eq!(royalty_shares(&[50, 50], 7).unwrap(), vec![4, 3]);
+
+        let amounts = royalty_shares(&[1, 2, 97], 12_345).unwrap();
+        assert_eq!(amounts.iter().sum::<u64>(), 12_345);
+    }
+
+    #[test]
+    fn royalty_shares_reject_zero_total() {
+        assert!(royalty_shares(&[0, 0], 100).is_err());
+    }
+
+    fn hash_pair(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        if a <= b {
+            keccak::hashv(&[&a, &b]).0
+        } else {
+            keccak::hashv(&[&b, &a]).0
+        }
+    }
+
+    #[test]
+    fn merkle_proof_accepts_every_allowlisted_leaf() {
+        let wallets: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let leaves: Vec<[u8; 32]> = wallets
+            .iter()
+            .enumerate()
+            .map(|(i, wallet)| allowlist_leaf(wallet, i as u32))
+            .collect();
+        let left = hash_pair(leaves[0], leaves[1]);
+        let right = hash_pair(leaves[2], leaves[3]);
+        let root = hash_pair(left, right);
+
+        assert!(verify_merkle_proof(&[leaves[1], right], root, leaves[0]));
+        assert!(verify_merkle_proof(&[leaves[0], right], root, leaves[1]));
+        assert!(verify_merkle_proof(&[leaves[3], left], root, leaves[2]));
+        assert!(verify_merkle_proof(&[leaves[2], left], root, leaves[3]));
+    }
+
+    #[test]
+    fn merkle_proof_rejects_wrong_leaf_or_proof() {
+        let wallet = Pubkey::new_unique();
+        let sibling = allowlist_leaf(&Pubkey::new_unique(), 0);
+        let leaf = allowlist_leaf(&wallet, 2);
+        let root = hash_pair(leaf, sibling);
+
+        assert!(verify_merkle_proof(&[sibling], root, leaf));
+        // Claiming a bigger allowance changes the leaf
+        assert!(!verify_merkle_proof(&[sibling], root, allowlist_leaf(&wallet, 3)));
+        assert!(!verify_merkle_proof(&[], root, leaf));
+        assert!(!verify_merkle_proof(&[sibling, sibling], root, leaf));
+    }
+
+    #[test]
+    fn supply_cap_can_only_tighten() {
+        // Uncapped can become capped, but not below what was minted
+        assert!(is_tighter_cap(0, 100, 40));
+        assert!(is_tighter_cap(0, 40, 40));
+        assert!(!is_tighter_cap(0, 39, 40));
+        assert!(is_tighter_cap(0, 0, 40));
+
+        // A cap can be lowered but never raised or removed
+        assert!(is_tighter_cap(100, 50, 10));
+        assert!(is_tighter_cap(100, 100, 10));
+        assert!(!is_tighter_cap(100, 101, 10));
+        assert!(!is_tighter_cap(100, 0, 10));
+        assert!(!is_tighter_cap(100, 5, 10));
+    }
+
+    fn auction(reserve_price: u64, min_increment_bps: u16, highest_bid: Option<u64>) -> Auction {
+        Auction {
+            seller: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            reserve_price,
+            min_increment_bps,
+            highest_bid: highest_bid.unwrap_or(0),
+            highest_bidder: highest_bid.map(|_| Pubkey::new_unique()),
+            end_at: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn first_bid_must_meet_reserve() {
+        assert_eq!(auction(1_000_000, 500, None).min_next_bid().unwrap(), 1_000_000);
+    }
+
+    #[test]
+    fn later_bids_must_beat_the_current_one_by_the_increment() {
+        assert_eq!(
+            auction(1_000_000, 500, Some(2_000_000)).min_next_bid().unwrap(),
+            2_100_000
+        );
+        // Bids always go up by at least one lamport
+        assert_eq!(auction(1, 0, Some(2_000_000)).min_next_bid().unwrap(), 2_000_001);
+        assert_eq!(auction(1, 500, Some(10)).min_next_bid().unwrap(), 11);
+        assert!(auction(1, 500, Some(u64::MAX)).min_next_bid().is_err());
+    }
+
+    fn dutch(floor_price: u64, duration: i64, step_interval: i64) -> DutchPricing {
+        DutchPricing {
+            floor_price,
+            duration,
+            step_interval,
+        }
+    }
+
+    #[test]
+    fn dutch_price_decays_linearly_to_the_floor() {
+        let pricing = dutch(400, 100, 0);
+        assert_eq!(pricing.price_at(1_000, -5).unwrap(), 1_000);
+        assert_eq!(pricing.price_at(1_000, 0).unwrap(), 1_000);
+        assert_eq!(pricing.price_at(1_000, 50).unwrap(), 700);
+        assert_eq!(pricing.price_at(1_000, 99).unwrap(), 406);
+        assert_eq!(pricing.price_at(1_000, 100).unwrap(), 400);
+        assert_eq!(pricing.price_at(1_000, 10_000).unwrap(), 400);
+    }
+
+    #[test]
+    fn dutch_price_drops_once_per_step() {
+        let pricing = dutch(400, 100, 25);
+        assert_eq!(pricing.price_at(1_000, 24).unwrap(), 1_000);
+        assert_eq!(pricing.price_at(1_000, 25).unwrap(), 850);
+        assert_eq!(pricing.price_at(1_000, 49).unwrap(), 850);
+        assert_eq!(pricing.price_at(1_000, 75).unwrap(), 550);
+        assert_eq!(pricing.price_at(1_000, 100).unwrap(), 400);
+    }
+
+    #[test]
+    fn dutch_pricing_validation() {
+        assert!(dutch(400, 100, 25).validate(1_000).is_ok());
+        assert!(dutch(400, 100, 100).validate(1_000).is_ok());
+        assert!(dutch(0, 100, 0).validate(1_000).is_err());
+        assert!(dutch(1_000, 100, 0).validate(1_000).is_err());
+        assert!(dutch(400, 0, 0).validate(1_000).is_err());
+        assert!(dutch(400, 100, 101).validate(1_000).is_err());
+        assert!(dutch(400, 100, -1).validate(1_000).is_err());
+    }
+
+    fn card(attack: u8, defense: u8, element: ElementType, rarity: RarityType) -> CardStats {
+        CardStats {
+            mint: Pubkey::new_unique(),
+            attack,
+            defense,
+            element,
+            rarity,
+        }
+    }
+
+    #[test]
+    fn empty_filter_matches_any_card() {
+        let filter = CardFilter::default();
+        assert!(filter.matches(&card(0, 0, ElementType::Toxic, RarityType::Common)));
+        assert!(filter.matches(&card(255, 255, ElementType::Cursed, RarityType::GodTier)));
+    }
+
+    #[test]
+    fn filter_requires_every_set_trait() {
+        let filter = CardFilter {
+            element: Some(ElementType::Dank),
+            rarity: Some(RarityType::Epic),
+            min_attack: Some(50),
+            min_defense: Some(30),
+        };
+        assert!(filter.matches(&card(50, 30, ElementType::Dank, RarityType::Epic)));
+        assert!(filter.matches(&card(90, 80, ElementType::Dank, RarityType::Epic)));
+
+        assert!(!filter.matches(&card(50, 30, ElementType::Toxic, RarityType::Epic)));
+        assert!(!filter.matches(&card(50, 30, ElementType::Dank, RarityType::Rare)));
+        assert!(!filter.matches(&card(49, 30, ElementType::Dank, RarityType::Epic)));
+        assert!(!filter.matches(&card(50, 29, ElementType::Dank, RarityType::Epic)));
+    }
+
+    fn pending(effective_at: i64) -> PendingChange {
+        PendingChange {
+            id: 0,
+            payer: Pubkey::default(),
+            change: ConfigChange::TimelockDelay(0),
+            effective_at,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn pending_change_waits_out_the_delay() {
+        let effective_at = PendingChange::effective_at(1_000, 3_600).unwrap();
+        assert_eq!(effective_at, 4_600);
+
+        let change = pending(effective_at);
+        assert!(!change.is_ready(1_000));
+        assert!(!change.is_ready(4_599));
+        assert!(change.is_ready(4_600));
+        assert!(change.is_ready(10_000));
+    }
+
+    #[test]
+    fn pending_change_without_delay_is_ready_at_once() {
+        let effective_at = PendingChange::effective_at(1_000, 0).unwrap();
+        assert!(pending(effective_at).is_ready(1_000));
+    }
+
+    #[test]
+    fn pending_change_rejects_overflowing_delay() {
+        assert!(PendingChange::effective_at(i64::MAX, 1).is_err());
+    }
 }
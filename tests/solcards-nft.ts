@@ -1,11 +1,18 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { NftProgram } from "../target/types/nft_program";
+import { assert } from "chai";
 import { ComputeBudgetProgram } from "@solana/web3.js";
 import {
   TOKEN_PROGRAM_ID,
   ASSOCIATED_TOKEN_PROGRAM_ID,
   getAssociatedTokenAddress,
+  getAssociatedTokenAddressSync,
+  getAccount,
+  getOrCreateAssociatedTokenAccount,
+  createMint,
+  mintTo,
+  revoke,
 } from "@solana/spl-token";
 import {
   PublicKey,
@@ -15,10 +22,12 @@ import {
 } from "@solana/web3.js";
 import {
   PROGRAM_ID as TOKEN_METADATA_PROGRAM_ID,
+  Metadata,
   createCreateMetadataAccountV3Instruction,
   createCreateMasterEditionV3Instruction,
   isCollectionDetailsToggleClear,
 } from "@metaplex-foundation/mpl-token-metadata";
+import { keccak_256 } from "@noble/hashes/sha3";
 
 describe("nft_program", () => {
 
@@ -48,11 +57,298 @@ describe("nft_program", () => {
   let collectionMint: Keypair;
   let nftMint: Keypair;
 
+  const [configPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    program.programId
+  );
+  const [treasuryPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("treasury")],
+    program.programId
+  );
+  const [programDataPda] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+
+  const metadataPda = (mint: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("metadata"), TOKEN_METADATA_PROGRAM_ID.toBuffer(), mint.toBuffer()],
+      TOKEN_METADATA_PROGRAM_ID
+    )[0];
+  const masterEditionPda = (mint: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("metadata"),
+        TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+        mint.toBuffer(),
+        Buffer.from("edition"),
+      ],
+      TOKEN_METADATA_PROGRAM_ID
+    )[0];
+  const statsPda = (mint: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from("stats"), mint.toBuffer()], program.programId)[0];
+  const listingPda = (mint: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from("listing"), mint.toBuffer()], program.programId)[0];
+  const registryPda = () =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("collection"), collectionMint.publicKey.toBuffer()],
+      program.programId
+    )[0];
+  const collectionAuthorityRecordPda = () =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("metadata"),
+        TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+        collectionMint.publicKey.toBuffer(),
+        Buffer.from("collection_authority"),
+        registryPda().toBuffer(),
+      ],
+      TOKEN_METADATA_PROGRAM_ID
+    )[0];
+
+  const airdrop = async (to: PublicKey, sol = 10) => {
+    const sig = await provider.connection.requestAirdrop(
+      to,
+      sol * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(sig, "confirmed");
+  };
+
+  const balance = (account: PublicKey) => provider.connection.getBalance(account, "confirmed");
+
+  const tokenBalance = async (account: PublicKey) => {
+    const info = await provider.connection.getAccountInfo(account);
+    if (!info) return 0;
+    const { value } = await provider.connection.getTokenAccountBalance(account);
+    return parseInt(value.amount);
+  };
+
+  const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+  const chainTime = async () =>
+    provider.connection.getBlockTime(await provider.connection.getSlot("confirmed"));
+
+  // Waits until the cluster clock has passed `timestamp`
+  const waitUntil = async (timestamp: number) => {
+    while ((await chainTime()) <= timestamp) {
+      await sleep(500);
+    }
+  };
+
+  const expectError = async (promise: Promise<unknown>, code: string) => {
+    try {
+      await promise;
+    } catch (error) {
+      assert.include(error.toString(), code);
+      return;
+    }
+    assert.fail(`Expected ${code}`);
+  };
+
+  // Cards minted co-signed by the collection authority have it as their only
+  // verified creator, so it is the one royalty account purchases pass in
+  const creatorAccounts = () => [
+    { pubkey: COLLECTION_AUTHORITY, isSigner: false, isWritable: true },
+  ];
+
+  const mintCounterPda = (payer: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("mint_counter"), collectionMint.publicKey.toBuffer(), payer.toBuffer()],
+      program.programId
+    )[0];
+
+  type Stats = { attack?: number; defense?: number; element?: any; rarity?: any };
+
+  const cardStats = (mint: PublicKey, stats: Stats) => ({
+    mint,
+    attack: stats.attack ?? 50,
+    defense: stats.defense ?? 50,
+    element: stats.element ?? { dank: {} },
+    rarity: stats.rarity ?? { common: {} },
+  });
+
+  // Public mints leave out the collection authority and count against the
+  // payer's mint counter instead
+  const mintAccounts = async (owner: PublicKey, mint: PublicKey, publicMint: boolean) => ({
+    payer: owner,
+    statsAccount: statsPda(mint),
+    mint,
+    metadata: metadataPda(mint),
+    masterEdition: masterEditionPda(mint),
+    tokenAccount: await getAssociatedTokenAddress(mint, owner),
+    tokenProgram: TOKEN_PROGRAM_ID,
+    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    metadataProgram: TOKEN_METADATA_PROGRAM_ID,
+    systemProgram: SystemProgram.programId,
+    rent: SYSVAR_RENT_PUBKEY,
+    collectionMint: collectionMint.publicKey,
+    collectionMetadata: metadataPda(collectionMint.publicKey),
+    collectionMasterEdition: masterEditionPda(collectionMint.publicKey),
+    collectionAuthority: publicMint ? null : COLLECTION_AUTHORITY,
+    collectionAuthorityRecord: collectionAuthorityRecordPda(),
+    config: configPda,
+    collectionRegistry: registryPda(),
+    mintCounter: publicMint ? mintCounterPda(owner) : null,
+  });
+
+  // Mints a card into `owner`'s wallet, co-signed by the collection authority
+  // unless `publicMint` is set, in which case `owner` pays the collection's price
+  const mintCard = async (owner: Keypair, stats: Stats = {}, { publicMint = false } = {}) => {
+    const mint = Keypair.generate();
+    await program.methods
+      .mintNft(nftName, nftSymbol, nftUri, cardStats(mint.publicKey, stats))
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 300000 })])
+      .accountsStrict(await mintAccounts(owner.publicKey, mint.publicKey, publicMint))
+      .signers([mint, owner])
+      .rpc({ commitment: "confirmed" });
+    return mint.publicKey;
+  };
+
+  type ListingOptions = {
+    dutch?: { floorPrice: anchor.BN; duration: anchor.BN; stepInterval: anchor.BN };
+    expiresAt?: anchor.BN;
+    reservedBuyer?: PublicKey;
+    paymentMint?: PublicKey;
+  };
+
+  const listCard = async (
+    seller: Keypair,
+    mint: PublicKey,
+    price: number,
+    options: ListingOptions = {}
+  ) =>
+    program.methods
+      .listNft(
+        new anchor.BN(price),
+        options.paymentMint ?? null,
+        options.dutch ?? null,
+        options.expiresAt ?? null,
+        options.reservedBuyer ?? null
+      )
+      .accountsStrict({
+        seller: seller.publicKey,
+        listing: listingPda(mint),
+        mint,
+        tokenAccount: await getAssociatedTokenAddress(mint, seller.publicKey),
+        config: configPda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([seller])
+      .rpc();
+
+  const purchaseCard = async (buyer: Keypair, seller: PublicKey, mint: PublicKey) =>
+    program.methods
+      .purchaseNft()
+      .accountsStrict({
+        buyer: buyer.publicKey,
+        seller,
+        config: configPda,
+        treasury: treasuryPda,
+        listing: listingPda(mint),
+        mint,
+        metadata: metadataPda(mint),
+        sellerToken: await getAssociatedTokenAddress(mint, seller),
+        vault: null,
+        buyerToken: await getAssociatedTokenAddress(mint, buyer.publicKey),
+        buyerPaymentAccount: null,
+        sellerPaymentAccount: null,
+        treasuryPaymentAccount: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts(creatorAccounts())
+      .signers([buyer])
+      .rpc({ commitment: "confirmed" });
+
+  const cancelCard = async (seller: Keypair, mint: PublicKey) =>
+    program.methods
+      .cancelListing()
+      .accountsStrict({
+        seller: seller.publicKey,
+        listing: listingPda(mint),
+        mint,
+        tokenAccount: await getAssociatedTokenAddress(mint, seller.publicKey),
+        vault: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([seller])
+      .rpc({ commitment: "confirmed" });
+
+  const pendingChangePda = (id: anchor.BN) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("pending_change"), id.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+
+  // Queues a config change as the marketplace authority (the provider wallet
+  // unless given) and returns its id
+  const queueChange = async (change: any, authority?: Keypair) => {
+    const { nextChangeId } = await program.account.marketplaceConfig.fetch(configPda);
+    await program.methods
+      .queueConfigChange(change)
+      .accountsStrict({
+        authority: authority?.publicKey ?? provider.wallet.publicKey,
+        config: configPda,
+        adminSet: null,
+        proposal: null,
+        proposer: null,
+        pendingChange: pendingChangePda(nextChangeId),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers(authority ? [authority] : [])
+      .rpc({ commitment: "confirmed" });
+    return nextChangeId;
+  };
+
+  const executeChange = async (id: anchor.BN, payer = provider.wallet.publicKey) =>
+    program.methods
+      .executePendingChange()
+      .accountsStrict({
+        config: configPda,
+        pendingChange: pendingChangePda(id),
+        payer,
+      })
+      .rpc({ commitment: "confirmed" });
+
+  // Queues `change` and executes it once its timelock has passed
+  const applyChange = async (change: any, authority?: Keypair) => {
+    const id = await queueChange(change, authority);
+    const { effectiveAt } = await program.account.pendingChange.fetch(pendingChangePda(id));
+    await waitUntil(effectiveAt.toNumber() - 1);
+    await executeChange(id, authority?.publicKey);
+  };
+
   before(async () => {
     // Generate new keypair for collection mint
     collectionMint = Keypair.generate();
   });
 
+  it("Can initialize the marketplace config", async () => {
+    const existing = await provider.connection.getAccountInfo(configPda);
+    if (existing) {
+      console.log("Config already initialized");
+      return;
+    }
+
+    await program.methods
+      .initializeConfig(provider.wallet.publicKey, 300, 200, new anchor.BN(0))
+      .accountsStrict({
+        authority: provider.wallet.publicKey,
+        program: program.programId,
+        programData: programDataPda,
+        config: configPda,
+        treasury: treasuryPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const config = await program.account.marketplaceConfig.fetch(configPda);
+    console.log("Config authority:", config.authority.toString());
+  });
+
   it("Can create a collection", async () => {
 
     const bytes = [
@@ -91,6 +387,22 @@ describe("nft_program", () => {
       TOKEN_METADATA_PROGRAM_ID
     );
 
+    const [collectionRegistry] = PublicKey.findProgramAddressSync(
+      [Buffer.from("collection"), collectionMint.publicKey.toBuffer()],
+      program.programId
+    );
+
+    const [collectionAuthorityRecord] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("metadata"),
+        TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+        collectionMint.publicKey.toBuffer(),
+        Buffer.from("collection_authority"),
+        collectionRegistry.toBuffer(),
+      ],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+
     // Get associated token account for collection
     const collectionTokenAccount = await getAssociatedTokenAddress(
       collectionMint.publicKey,
@@ -107,10 +419,16 @@ describe("nft_program", () => {
         .accountsStrict({
           payer: provider.wallet.publicKey,
           collectionAuthority: COLLECTION_AUTHORITY,
+          config: configPda,
+          adminSet: null,
+          proposal: null,
+          proposer: null,
+          collectionRegistry: collectionRegistry,
           collectionMint: collectionMint.publicKey,
           collectionTokenAccount: collectionTokenAccount,
           collectionMetadata: collectionMetadata,
           collectionMasterEdition: collectionMasterEdition,
+          collectionAuthorityRecord: collectionAuthorityRecord,
           tokenProgram: TOKEN_PROGRAM_ID,
           associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
           metadataProgram: TOKEN_METADATA_PROGRAM_ID,
@@ -207,6 +525,22 @@ describe("nft_program", () => {
     rarity: { common: {} },
   };
 
+    const [collectionRegistry] = PublicKey.findProgramAddressSync(
+      [Buffer.from("collection"), collectionMint.publicKey.toBuffer()],
+      program.programId
+    );
+
+    const [collectionAuthorityRecord] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("metadata"),
+        TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+        collectionMint.publicKey.toBuffer(),
+        Buffer.from("collection_authority"),
+        collectionRegistry.toBuffer(),
+      ],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+
     // Get stats account PDA
     const [statsAccount] = PublicKey.findProgramAddressSync(
       [Buffer.from("stats"), nftMint.publicKey.toBuffer()],
@@ -219,7 +553,7 @@ describe("nft_program", () => {
       // Log important constants
 console.log("Program ID:", program.programId.toString());
 console.log("COLLECTION_AUTHORITY in test:", COLLECTION_AUTHORITY.toString());
-console.log("Hardcoded COLLECTION_AUTHORITY in Rust:", "DE:FA:58:1B:B6:DB:EC:4F:10:B7:BB:E2:CF:A9:C9:9F:02:90:2C:54:37:91:A6:1F:C1:9D:24:06:40:77:36:21");
+console.log("Config PDA:", configPda.toString());
 
 // Make sure these match!
       
@@ -263,6 +597,11 @@ console.log("Hardcoded COLLECTION_AUTHORITY in Rust:", "DE:FA:58:1B:B6:DB:EC:4F:
           collectionMetadata: collectionMetadata,
           collectionMasterEdition: collectionMasterEdition,
           collectionAuthority: COLLECTION_AUTHORITY,
+          collectionAuthorityRecord: collectionAuthorityRecord,
+          config: configPda,
+          collectionRegistry: collectionRegistry,
+          // Co-signed mints don't count against wallet limits
+          mintCounter: null,
         })
         .signers([nftMint,payer])
         .rpc();
@@ -288,4 +627,1600 @@ console.log("Hardcoded COLLECTION_AUTHORITY in Rust:", "DE:FA:58:1B:B6:DB:EC:4F:
       throw error;
     }
   });
-});
\ No newline at end of file
+
+  describe("listing lifecycle", () => {
+    const seller = Keypair.generate();
+    const buyer = Keypair.generate();
+    let mint: PublicKey;
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(buyer.publicKey);
+      mint = await mintCard(seller);
+    });
+
+    it("Lists, reprices and cancels a card", async () => {
+      await listCard(seller, mint, 1_000_000_000);
+
+      await program.methods
+        .updateListing(new anchor.BN(1_500_000_000), null)
+        .accountsStrict({
+          seller: seller.publicKey,
+          listing: listingPda(mint),
+          mint,
+          tokenAccount: await getAssociatedTokenAddress(mint, seller.publicKey),
+          config: configPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+      let listing = await program.account.listingData.fetch(listingPda(mint));
+      assert.equal(listing.price.toNumber(), 1_500_000_000);
+
+      await cancelCard(seller, mint);
+
+      listing = await program.account.listingData.fetch(listingPda(mint));
+      assert.deepEqual(listing.status, { notActive: {} });
+      assert.deepEqual(
+        listing.history.map((entry) => Object.keys(entry.action)[0]),
+        ["list", "updatePrice", "cancel"]
+      );
+    });
+
+    it("Sells a card to a buyer who can list it again", async () => {
+      await listCard(seller, mint, 1_000_000_000);
+      await purchaseCard(buyer, seller.publicKey, mint);
+
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, buyer.publicKey)), 1);
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, seller.publicKey)), 0);
+
+      await listCard(buyer, mint, 2_000_000_000);
+      const listing = await program.account.listingData.fetch(listingPda(mint));
+      assert.ok(listing.seller.equals(buyer.publicKey));
+      assert.deepEqual(listing.status, { active: {} });
+
+      await cancelCard(buyer, mint);
+    });
+  });
+
+  describe("sale settlement", () => {
+    const seller = Keypair.generate();
+    const buyer = Keypair.generate();
+    // Royalty and platform fee the config was initialized with
+    const ROYALTY_BPS = 300;
+    const PLATFORM_FEE_BPS = 200;
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(buyer.publicKey, 20);
+    });
+
+    it("Pays the royalty to the card's creator and the rounding dust to the seller", async () => {
+      const mint = await mintCard(seller);
+      const price = 1_000_000_033;
+      await listCard(seller, mint, price);
+
+      const creatorBefore = await balance(COLLECTION_AUTHORITY);
+      const sellerBefore = await balance(seller.publicKey);
+      const signature = await purchaseCard(buyer, seller.publicKey, mint);
+      const tx = await provider.connection.getTransaction(signature, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+
+      const royalty = Math.floor((price * ROYALTY_BPS) / 10_000);
+      const platformFee = Math.floor((price * PLATFORM_FEE_BPS) / 10_000);
+      // The provider wallet is both the card's creator and the fee payer
+      assert.equal((await balance(COLLECTION_AUTHORITY)) - creatorBefore + tx.meta.fee, royalty);
+      assert.equal((await balance(seller.publicKey)) - sellerBefore, price - royalty - platformFee);
+    });
+
+    it("Records the royalty and platform fee in the trade history", async () => {
+      const mint = await mintCard(seller);
+      const price = 2_000_000_000;
+      await listCard(seller, mint, price);
+
+      const treasuryBefore = await balance(treasuryPda);
+      await purchaseCard(buyer, seller.publicKey, mint);
+
+      const royalty = (price * ROYALTY_BPS) / 10_000;
+      const platformFee = (price * PLATFORM_FEE_BPS) / 10_000;
+      assert.equal((await balance(treasuryPda)) - treasuryBefore, platformFee);
+
+      const listing = await program.account.listingData.fetch(listingPda(mint));
+      const sale = listing.history[listing.history.length - 1];
+      assert.deepEqual(sale.action, { purchase: {} });
+      assert.equal(sale.price.toNumber(), price);
+      assert.equal(sale.royalty.toNumber(), royalty);
+      assert.equal(sale.platformFee.toNumber(), platformFee);
+    });
+  });
+
+  describe("auctions", () => {
+    const seller = Keypair.generate();
+    const alice = Keypair.generate();
+    const bob = Keypair.generate();
+
+    const auctionPda = (mint: PublicKey) =>
+      PublicKey.findProgramAddressSync([Buffer.from("auction"), mint.toBuffer()], program.programId)[0];
+
+    const createAuction = async (mint: PublicKey, reservePrice: number, endAt: number) =>
+      program.methods
+        .createAuction(new anchor.BN(reservePrice), new anchor.BN(endAt), 500)
+        .accountsStrict({
+          seller: seller.publicKey,
+          auction: auctionPda(mint),
+          mint,
+          tokenAccount: await getAssociatedTokenAddress(mint, seller.publicKey),
+          config: configPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+    const placeBid = (
+      bidder: Keypair,
+      mint: PublicKey,
+      amount: number,
+      previousBidder: PublicKey | null
+    ) =>
+      program.methods
+        .placeBid(new anchor.BN(amount))
+        .accountsStrict({
+          bidder: bidder.publicKey,
+          auction: auctionPda(mint),
+          previousBidder,
+          config: configPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([bidder])
+        .rpc({ commitment: "confirmed" });
+
+    const settleAuction = async (mint: PublicKey, winner: PublicKey) =>
+      program.methods
+        .settleAuction()
+        .accountsStrict({
+          payer: provider.wallet.publicKey,
+          seller: seller.publicKey,
+          winner,
+          config: configPda,
+          treasury: treasuryPda,
+          auction: auctionPda(mint),
+          listing: listingPda(mint),
+          mint,
+          metadata: metadataPda(mint),
+          sellerToken: await getAssociatedTokenAddress(mint, seller.publicKey),
+          winnerToken: await getAssociatedTokenAddress(mint, winner),
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(creatorAccounts())
+        .rpc({ commitment: "confirmed" });
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(alice.publicKey);
+      await airdrop(bob.publicKey);
+    });
+
+    it("Refunds the outbid bidder and settles to the highest bid", async () => {
+      const mint = await mintCard(seller);
+      const endAt = (await chainTime()) + 6;
+      await createAuction(mint, 100_000_000, endAt);
+
+      await expectError(placeBid(alice, mint, 50_000_000, null), "BidTooLow");
+      await placeBid(alice, mint, 100_000_000, null);
+      // Bids must clear the 5% increment
+      await expectError(placeBid(bob, mint, 104_000_000, alice.publicKey), "BidTooLow");
+
+      const aliceBefore = await balance(alice.publicKey);
+      await placeBid(bob, mint, 105_000_000, alice.publicKey);
+      assert.equal((await balance(alice.publicKey)) - aliceBefore, 100_000_000);
+
+      await expectError(settleAuction(mint, bob.publicKey), "AuctionNotEnded");
+      await waitUntil(endAt);
+      await settleAuction(mint, bob.publicKey);
+
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, bob.publicKey)), 1);
+      const listing = await program.account.listingData.fetch(listingPda(mint));
+      const sale = listing.history[listing.history.length - 1];
+      assert.deepEqual(sale.action, { auctionSettled: {} });
+      assert.equal(sale.price.toNumber(), 105_000_000);
+      assert.ok(listing.seller.equals(bob.publicKey));
+    });
+
+    it("Refunds the winner when the seller revoked the auction's delegate", async () => {
+      const mint = await mintCard(seller);
+      const endAt = (await chainTime()) + 4;
+      await createAuction(mint, 100_000_000, endAt);
+      await placeBid(alice, mint, 100_000_000, null);
+
+      const sellerToken = await getAssociatedTokenAddress(mint, seller.publicKey);
+      await revoke(provider.connection, seller, sellerToken, seller);
+
+      await waitUntil(endAt);
+      const aliceBefore = await balance(alice.publicKey);
+      await settleAuction(mint, alice.publicKey);
+
+      assert.equal((await balance(alice.publicKey)) - aliceBefore, 100_000_000);
+      assert.equal(await tokenBalance(sellerToken), 1);
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, alice.publicKey)), 0);
+    });
+  });
+
+  describe("dutch listings", () => {
+    const seller = Keypair.generate();
+    const buyer = Keypair.generate();
+    const START_PRICE = 1_000_000_000;
+    const FLOOR_PRICE = 500_000_000;
+    const DURATION = 8;
+
+    const dutch = {
+      floorPrice: new anchor.BN(FLOOR_PRICE),
+      duration: new anchor.BN(DURATION),
+      stepInterval: new anchor.BN(0),
+    };
+
+    // Price the program should charge `elapsed` seconds into the schedule
+    const expectedPrice = (elapsed: number) =>
+      elapsed >= DURATION
+        ? FLOOR_PRICE
+        : START_PRICE - Math.floor(((START_PRICE - FLOOR_PRICE) * elapsed) / DURATION);
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(buyer.publicKey);
+    });
+
+    it("Charges the decayed price part way through the schedule", async () => {
+      const mint = await mintCard(seller);
+      await listCard(seller, mint, START_PRICE, { dutch });
+      await purchaseCard(buyer, seller.publicKey, mint);
+
+      const listing = await program.account.listingData.fetch(listingPda(mint));
+      const sale = listing.history[listing.history.length - 1];
+      const elapsed = sale.timestamp.toNumber() - listing.createdAt.toNumber();
+      assert.equal(sale.price.toNumber(), expectedPrice(elapsed));
+      assert.isAtMost(sale.price.toNumber(), START_PRICE);
+      assert.isAtLeast(sale.price.toNumber(), FLOOR_PRICE);
+    });
+
+    it("Charges the floor price once the schedule has run out", async () => {
+      const mint = await mintCard(seller);
+      await listCard(seller, mint, START_PRICE, { dutch });
+      const listed = await program.account.listingData.fetch(listingPda(mint));
+      await waitUntil(listed.createdAt.toNumber() + DURATION);
+
+      await purchaseCard(buyer, seller.publicKey, mint);
+
+      const listing = await program.account.listingData.fetch(listingPda(mint));
+      const sale = listing.history[listing.history.length - 1];
+      assert.equal(sale.price.toNumber(), FLOOR_PRICE);
+    });
+
+    it("Rejects repricing a dutch listing", async () => {
+      const mint = await mintCard(seller);
+      await listCard(seller, mint, START_PRICE, { dutch });
+
+      await expectError(
+        program.methods
+          .updateListing(new anchor.BN(START_PRICE * 2), null)
+          .accountsStrict({
+            seller: seller.publicKey,
+            listing: listingPda(mint),
+            mint,
+            tokenAccount: await getAssociatedTokenAddress(mint, seller.publicKey),
+            config: configPda,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([seller])
+          .rpc(),
+        "DutchListingNotUpdatable"
+      );
+    });
+  });
+
+  describe("offers", () => {
+    const seller = Keypair.generate();
+    const bidder = Keypair.generate();
+    const OFFER = 500_000_000;
+
+    const offerPda = (mint: PublicKey) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("offer"), mint.toBuffer(), bidder.publicKey.toBuffer()],
+        program.programId
+      )[0];
+
+    const makeOffer = (mint: PublicKey) =>
+      program.methods
+        .makeOffer(new anchor.BN(OFFER))
+        .accountsStrict({
+          bidder: bidder.publicKey,
+          offer: offerPda(mint),
+          mint,
+          config: configPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([bidder])
+        .rpc({ commitment: "confirmed" });
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(bidder.publicKey);
+    });
+
+    it("Returns the escrowed bid when an offer is cancelled", async () => {
+      const mint = await mintCard(seller);
+      const bidderBefore = await balance(bidder.publicKey);
+
+      await makeOffer(mint);
+      assert.isAtLeast(await balance(offerPda(mint)), OFFER);
+
+      await program.methods
+        .cancelOffer()
+        .accountsStrict({ bidder: bidder.publicKey, offer: offerPda(mint) })
+        .signers([bidder])
+        .rpc({ commitment: "confirmed" });
+
+      assert.equal(await balance(bidder.publicKey), bidderBefore);
+      assert.isNull(await program.account.offer.fetchNullable(offerPda(mint)));
+    });
+
+    it("Sells an unlisted card to the bidder when the holder accepts", async () => {
+      const mint = await mintCard(seller);
+      await makeOffer(mint);
+
+      await program.methods
+        .acceptOffer()
+        .accountsStrict({
+          seller: seller.publicKey,
+          bidder: bidder.publicKey,
+          config: configPda,
+          treasury: treasuryPda,
+          offer: offerPda(mint),
+          listing: listingPda(mint),
+          mint,
+          metadata: metadataPda(mint),
+          sellerToken: await getAssociatedTokenAddress(mint, seller.publicKey),
+          bidderToken: await getAssociatedTokenAddress(mint, bidder.publicKey),
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(creatorAccounts())
+        .signers([seller])
+        .rpc({ commitment: "confirmed" });
+
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, bidder.publicKey)), 1);
+      assert.isNull(await program.account.offer.fetchNullable(offerPda(mint)));
+
+      const listing = await program.account.listingData.fetch(listingPda(mint));
+      const sale = listing.history[listing.history.length - 1];
+      assert.deepEqual(sale.action, { offerAccepted: {} });
+      assert.equal(sale.price.toNumber(), OFFER);
+      assert.equal(sale.royalty.toNumber() + sale.platformFee.toNumber(), (OFFER * 500) / 10_000);
+      assert.ok(listing.seller.equals(bidder.publicKey));
+    });
+  });
+
+  describe("collection offers", () => {
+    const seller = Keypair.generate();
+    const bidder = Keypair.generate();
+    const OFFER = 300_000_000;
+
+    const collectionOfferPda = (id: anchor.BN) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("collection_offer"),
+          collectionMint.publicKey.toBuffer(),
+          bidder.publicKey.toBuffer(),
+          id.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    const makeCollectionOffer = (id: anchor.BN, filter: any) =>
+      program.methods
+        .makeCollectionOffer(id, new anchor.BN(OFFER), filter)
+        .accountsStrict({
+          bidder: bidder.publicKey,
+          offer: collectionOfferPda(id),
+          collectionRegistry: registryPda(),
+          config: configPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([bidder])
+        .rpc();
+
+    const acceptCollectionOffer = async (id: anchor.BN, mint: PublicKey) =>
+      program.methods
+        .acceptCollectionOffer()
+        .accountsStrict({
+          seller: seller.publicKey,
+          bidder: bidder.publicKey,
+          config: configPda,
+          treasury: treasuryPda,
+          offer: collectionOfferPda(id),
+          listing: listingPda(mint),
+          mint,
+          metadata: metadataPda(mint),
+          stats: statsPda(mint),
+          sellerToken: await getAssociatedTokenAddress(mint, seller.publicKey),
+          bidderToken: await getAssociatedTokenAddress(mint, bidder.publicKey),
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(creatorAccounts())
+        .signers([seller])
+        .rpc();
+
+    const legendaryDank = {
+      element: { dank: {} },
+      rarity: { legendary: {} },
+      minAttack: 60,
+      minDefense: null,
+    };
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(bidder.publicKey);
+    });
+
+    it("Rejects a card that fails the offer's filter", async () => {
+      const id = new anchor.BN(1);
+      await makeCollectionOffer(id, legendaryDank);
+
+      const common = await mintCard(seller, { rarity: { common: {} } });
+      await expectError(acceptCollectionOffer(id, common), "CardFilterMismatch");
+
+      const weak = await mintCard(seller, { rarity: { legendary: {} }, attack: 59 });
+      await expectError(acceptCollectionOffer(id, weak), "CardFilterMismatch");
+
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(common, seller.publicKey)), 1);
+    });
+
+    it("Fills the offer with a card that matches the filter", async () => {
+      const id = new anchor.BN(2);
+      await makeCollectionOffer(id, legendaryDank);
+
+      const mint = await mintCard(seller, { rarity: { legendary: {} }, attack: 60 });
+      await acceptCollectionOffer(id, mint);
+
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, bidder.publicKey)), 1);
+      assert.isNull(await program.account.collectionOffer.fetchNullable(collectionOfferPda(id)));
+    });
+  });
+
+  describe("listing restrictions", () => {
+    const seller = Keypair.generate();
+    const buyer = Keypair.generate();
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(buyer.publicKey);
+    });
+
+    it("Rejects purchases of an expired listing and lets anyone expire it", async () => {
+      const mint = await mintCard(seller);
+      const expiresAt = (await chainTime()) + 3;
+      await listCard(seller, mint, 1_000_000_000, { expiresAt: new anchor.BN(expiresAt) });
+
+      await expectError(
+        program.methods
+          .expireListing()
+          .accountsStrict({ listing: listingPda(mint), seller: seller.publicKey })
+          .rpc(),
+        "ListingNotExpired"
+      );
+
+      await waitUntil(expiresAt);
+      await expectError(purchaseCard(buyer, seller.publicKey, mint), "ListingExpired");
+
+      await program.methods
+        .expireListing()
+        .accountsStrict({ listing: listingPda(mint), seller: seller.publicKey })
+        .rpc();
+
+      const listing = await program.account.listingData.fetch(listingPda(mint));
+      assert.deepEqual(listing.status, { notActive: {} });
+      assert.deepEqual(listing.history[listing.history.length - 1].action, { expired: {} });
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, seller.publicKey)), 1);
+
+      // The seller clears the delegate the expiry had to leave behind
+      const sellerToken = await getAssociatedTokenAddress(mint, seller.publicKey);
+      assert.ok((await getAccount(provider.connection, sellerToken)).delegate.equals(listingPda(mint)));
+      await cancelCard(seller, mint);
+      assert.isNull((await getAccount(provider.connection, sellerToken, "confirmed")).delegate);
+      const cancelled = await program.account.listingData.fetch(listingPda(mint), "confirmed");
+      assert.deepEqual(cancelled.history[cancelled.history.length - 1].action, { expired: {} });
+    });
+
+    it("Rejects an expiry in the past", async () => {
+      const mint = await mintCard(seller);
+      const past = new anchor.BN((await chainTime()) - 10);
+
+      await expectError(
+        listCard(seller, mint, 1_000_000_000, { expiresAt: past }),
+        "InvalidListingExpiry"
+      );
+    });
+
+    it("Only sells a reserved listing to its reserved buyer", async () => {
+      const mint = await mintCard(seller);
+      const outsider = Keypair.generate();
+      await airdrop(outsider.publicKey);
+      await listCard(seller, mint, 1_000_000_000, { reservedBuyer: buyer.publicKey });
+
+      await expectError(purchaseCard(outsider, seller.publicKey, mint), "ListingReserved");
+      await purchaseCard(buyer, seller.publicKey, mint);
+
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, buyer.publicKey)), 1);
+    });
+  });
+
+  describe("vault listings", () => {
+    const seller = Keypair.generate();
+    const buyer = Keypair.generate();
+
+    // The vault is the listing PDA's associated token account
+    const vaultAddress = (mint: PublicKey) =>
+      getAssociatedTokenAddress(mint, listingPda(mint), true);
+
+    const listInVault = async (mint: PublicKey, price: number) =>
+      program.methods
+        .listNftInVault(new anchor.BN(price), null, null, null, null)
+        .accountsStrict({
+          seller: seller.publicKey,
+          listing: listingPda(mint),
+          mint,
+          tokenAccount: await getAssociatedTokenAddress(mint, seller.publicKey),
+          vault: await vaultAddress(mint),
+          config: configPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(buyer.publicKey);
+    });
+
+    it("Holds the card in the vault and returns it on cancel", async () => {
+      const mint = await mintCard(seller);
+      const sellerToken = await getAssociatedTokenAddress(mint, seller.publicKey);
+      const vault = await vaultAddress(mint);
+
+      await listInVault(mint, 1_000_000_000);
+      assert.equal(await tokenBalance(sellerToken), 0);
+      assert.equal(await tokenBalance(vault), 1);
+      assert.isTrue((await program.account.listingData.fetch(listingPda(mint))).vaulted);
+
+      await program.methods
+        .cancelListing()
+        .accountsStrict({
+          seller: seller.publicKey,
+          listing: listingPda(mint),
+          mint,
+          tokenAccount: sellerToken,
+          vault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+      assert.equal(await tokenBalance(sellerToken), 1);
+      assert.isNull(await provider.connection.getAccountInfo(vault));
+      const listing = await program.account.listingData.fetch(listingPda(mint));
+      assert.isFalse(listing.vaulted);
+      assert.deepEqual(listing.status, { notActive: {} });
+    });
+
+    it("Sells the card straight out of the vault", async () => {
+      const mint = await mintCard(seller);
+      const vault = await vaultAddress(mint);
+      await listInVault(mint, 1_000_000_000);
+
+      await program.methods
+        .purchaseNft()
+        .accountsStrict({
+          buyer: buyer.publicKey,
+          seller: seller.publicKey,
+          config: configPda,
+          treasury: treasuryPda,
+          listing: listingPda(mint),
+          mint,
+          metadata: metadataPda(mint),
+          sellerToken: null,
+          vault,
+          buyerToken: await getAssociatedTokenAddress(mint, buyer.publicKey),
+          buyerPaymentAccount: null,
+          sellerPaymentAccount: null,
+          treasuryPaymentAccount: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(creatorAccounts())
+        .signers([buyer])
+        .rpc();
+
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, buyer.publicKey)), 1);
+      assert.isNull(await provider.connection.getAccountInfo(vault));
+      const listing = await program.account.listingData.fetch(listingPda(mint));
+      assert.isFalse(listing.vaulted);
+      assert.ok(listing.seller.equals(buyer.publicKey));
+    });
+  });
+
+  describe("bundle listings", () => {
+    const seller = Keypair.generate();
+    const buyer = Keypair.generate();
+
+    const bundlePda = (id: anchor.BN) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("bundle"), seller.publicKey.toBuffer(), id.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+
+    const createBundle = async (id: anchor.BN, mints: PublicKey[], price: number) => {
+      const cards = [];
+      for (const mint of mints) {
+        cards.push(
+          {
+            pubkey: await getAssociatedTokenAddress(mint, seller.publicKey),
+            isSigner: false,
+            isWritable: true,
+          },
+          { pubkey: listingPda(mint), isSigner: false, isWritable: false }
+        );
+      }
+      return program.methods
+        .createBundleListing(id, new anchor.BN(price))
+        .accountsStrict({
+          seller: seller.publicKey,
+          bundle: bundlePda(id),
+          config: configPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(cards)
+        .signers([seller])
+        .rpc({ commitment: "confirmed" });
+    };
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(buyer.publicKey);
+    });
+
+    it("Rejects a card that already has an active listing", async () => {
+      const listed = await mintCard(seller);
+      const unlisted = await mintCard(seller);
+      await listCard(seller, listed, 1_000_000_000);
+
+      await expectError(
+        createBundle(new anchor.BN(1), [listed, unlisted], 2_000_000_000),
+        "ListingAlreadyActive"
+      );
+
+      // The listing keeps its delegate
+      const token = await getAccount(
+        provider.connection,
+        await getAssociatedTokenAddress(listed, seller.publicKey)
+      );
+      assert.ok(token.delegate.equals(listingPda(listed)));
+      await cancelCard(seller, listed);
+    });
+
+    it("Sells every card in the bundle in one purchase", async () => {
+      const id = new anchor.BN(2);
+      const mints = [await mintCard(seller), await mintCard(seller)];
+      await createBundle(id, mints, 2_000_000_001);
+
+      const cards = [];
+      for (const mint of mints) {
+        cards.push(
+          { pubkey: mint, isSigner: false, isWritable: false },
+          { pubkey: metadataPda(mint), isSigner: false, isWritable: false },
+          {
+            pubkey: await getAssociatedTokenAddress(mint, seller.publicKey),
+            isSigner: false,
+            isWritable: true,
+          },
+          {
+            pubkey: await getAssociatedTokenAddress(mint, buyer.publicKey),
+            isSigner: false,
+            isWritable: true,
+          },
+          { pubkey: listingPda(mint), isSigner: false, isWritable: true },
+          ...creatorAccounts()
+        );
+      }
+
+      await program.methods
+        .purchaseBundle()
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 600000 })])
+        .accountsStrict({
+          buyer: buyer.publicKey,
+          seller: seller.publicKey,
+          config: configPda,
+          treasury: treasuryPda,
+          bundle: bundlePda(id),
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(cards)
+        .signers([buyer])
+        .rpc({ commitment: "confirmed" });
+
+      assert.isNull(await provider.connection.getAccountInfo(bundlePda(id)));
+      const prices = [];
+      for (const mint of mints) {
+        assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, buyer.publicKey)), 1);
+        const listing = await program.account.listingData.fetch(listingPda(mint));
+        assert.ok(listing.seller.equals(buyer.publicKey));
+        assert.deepEqual(listing.status, { notActive: {} });
+        prices.push(listing.history[listing.history.length - 1].price.toNumber());
+      }
+      // The price is split evenly, with the dust on the first card
+      assert.deepEqual(prices, [1_000_000_001, 1_000_000_000]);
+    });
+
+    it("Revokes the bundle's delegate when it is cancelled", async () => {
+      const id = new anchor.BN(3);
+      const mints = [await mintCard(seller), await mintCard(seller)];
+      await createBundle(id, mints, 2_000_000_000);
+
+      const tokenAccounts = [];
+      for (const mint of mints) {
+        tokenAccounts.push(await getAssociatedTokenAddress(mint, seller.publicKey));
+      }
+      assert.ok((await getAccount(provider.connection, tokenAccounts[0])).delegate.equals(bundlePda(id)));
+
+      await program.methods
+        .cancelBundleListing()
+        .accountsStrict({
+          seller: seller.publicKey,
+          bundle: bundlePda(id),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(
+          tokenAccounts.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }))
+        )
+        .signers([seller])
+        .rpc({ commitment: "confirmed" });
+
+      assert.isNull(await provider.connection.getAccountInfo(bundlePda(id)));
+      for (const tokenAccount of tokenAccounts) {
+        const token = await getAccount(provider.connection, tokenAccount, "confirmed");
+        assert.isNull(token.delegate);
+        assert.equal(Number(token.amount), 1);
+      }
+    });
+  });
+
+  describe("swaps", () => {
+    const proposer = Keypair.generate();
+    const counterparty = Keypair.generate();
+    const SWEETENER = 500_000_000;
+    const COUNTER_SWEETENER = 100_000_000;
+
+    const swapPda = (id: anchor.BN) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("swap"), proposer.publicKey.toBuffer(), id.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+
+    const proposeSwap = async (id: anchor.BN, offered: PublicKey, requested: PublicKey) =>
+      program.methods
+        .proposeSwap(
+          id,
+          counterparty.publicKey,
+          [requested],
+          new anchor.BN(SWEETENER),
+          new anchor.BN(COUNTER_SWEETENER)
+        )
+        .accountsStrict({
+          proposer: proposer.publicKey,
+          swap: swapPda(id),
+          config: configPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts([
+          {
+            pubkey: await getAssociatedTokenAddress(offered, proposer.publicKey),
+            isSigner: false,
+            isWritable: true,
+          },
+        ])
+        .signers([proposer])
+        .rpc({ commitment: "confirmed" });
+
+    const cardAccounts = async (mint: PublicKey, holder: PublicKey, receiver: PublicKey) => [
+      { pubkey: mint, isSigner: false, isWritable: false },
+      { pubkey: await getAssociatedTokenAddress(mint, holder), isSigner: false, isWritable: true },
+      { pubkey: await getAssociatedTokenAddress(mint, receiver), isSigner: false, isWritable: true },
+      { pubkey: listingPda(mint), isSigner: false, isWritable: true },
+    ];
+
+    before(async () => {
+      await airdrop(proposer.publicKey);
+      await airdrop(counterparty.publicKey);
+    });
+
+    it("Moves both sides' cards and sweeteners when accepted", async () => {
+      const id = new anchor.BN(1);
+      const offered = await mintCard(proposer);
+      const requested = await mintCard(counterparty);
+      await proposeSwap(id, offered, requested);
+
+      const swapLamports = await balance(swapPda(id));
+      const proposerBefore = await balance(proposer.publicKey);
+
+      await program.methods
+        .acceptSwap()
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 400000 })])
+        .accountsStrict({
+          counterparty: counterparty.publicKey,
+          proposer: proposer.publicKey,
+          swap: swapPda(id),
+          config: configPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts([
+          ...(await cardAccounts(offered, proposer.publicKey, counterparty.publicKey)),
+          ...(await cardAccounts(requested, counterparty.publicKey, proposer.publicKey)),
+        ])
+        .signers([counterparty])
+        .rpc({ commitment: "confirmed" });
+
+      assert.equal(
+        await tokenBalance(await getAssociatedTokenAddress(offered, counterparty.publicKey)),
+        1
+      );
+      assert.equal(
+        await tokenBalance(await getAssociatedTokenAddress(requested, proposer.publicKey)),
+        1
+      );
+      assert.isNull(await provider.connection.getAccountInfo(swapPda(id)));
+
+      // The proposer gets the swap's rent back plus the counterparty's sweetener
+      assert.equal(
+        (await balance(proposer.publicKey)) - proposerBefore,
+        swapLamports - SWEETENER + COUNTER_SWEETENER
+      );
+
+      const listing = await program.account.listingData.fetch(listingPda(requested));
+      assert.ok(listing.seller.equals(proposer.publicKey));
+      assert.deepEqual(listing.history[listing.history.length - 1].action, { swapped: {} });
+    });
+
+    it("Refunds the sweetener and revokes the delegate when cancelled", async () => {
+      const id = new anchor.BN(2);
+      const offered = await mintCard(proposer);
+      const requested = await mintCard(counterparty);
+      await proposeSwap(id, offered, requested);
+
+      const offeredToken = await getAssociatedTokenAddress(offered, proposer.publicKey);
+      assert.ok((await getAccount(provider.connection, offeredToken)).delegate.equals(swapPda(id)));
+
+      await program.methods
+        .cancelSwap()
+        .accountsStrict({
+          proposer: proposer.publicKey,
+          swap: swapPda(id),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts([{ pubkey: offeredToken, isSigner: false, isWritable: true }])
+        .signers([proposer])
+        .rpc({ commitment: "confirmed" });
+
+      assert.isNull(await provider.connection.getAccountInfo(swapPda(id)));
+      const token = await getAccount(provider.connection, offeredToken, "confirmed");
+      assert.isNull(token.delegate);
+      assert.equal(Number(token.amount), 1);
+    });
+  });
+
+  describe("config timelock", () => {
+    after(async () => {
+      await applyChange({ timelockDelay: { 0: new anchor.BN(0) } });
+    });
+
+    it("Holds a queued change until the delay has passed", async () => {
+      await applyChange({ timelockDelay: { 0: new anchor.BN(3) } });
+
+      const id = await queueChange({ platformFeeBps: { 0: 250 } });
+      await expectError(executeChange(id), "TimelockNotElapsed");
+
+      const { effectiveAt } = await program.account.pendingChange.fetch(pendingChangePda(id));
+      await waitUntil(effectiveAt.toNumber() - 1);
+      await executeChange(id);
+      assert.isNull(await provider.connection.getAccountInfo(pendingChangePda(id)));
+      assert.equal((await program.account.marketplaceConfig.fetch(configPda)).platformFeeBps, 250);
+
+      await applyChange({ platformFeeBps: { 0: 200 } });
+    });
+
+    it("Closes a cancelled change without applying it", async () => {
+      const id = await queueChange({ royaltyBps: { 0: 900 } });
+
+      await program.methods
+        .cancelPendingChange()
+        .accountsStrict({
+          authority: provider.wallet.publicKey,
+          config: configPda,
+          adminSet: null,
+          proposal: null,
+          proposer: null,
+          pendingChange: pendingChangePda(id),
+          payer: provider.wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      assert.isNull(await provider.connection.getAccountInfo(pendingChangePda(id)));
+      assert.equal((await program.account.marketplaceConfig.fetch(configPda)).royaltyBps, 300);
+    });
+  });
+
+  describe("token payments", () => {
+    const seller = Keypair.generate();
+    const buyer = Keypair.generate();
+    const mintAuthority = Keypair.generate();
+    const PRICE = 1_000_000;
+    // Royalty and platform fee the config was initialized with
+    const ROYALTY = (PRICE * 300) / 10_000;
+    const PLATFORM_FEE = (PRICE * 200) / 10_000;
+    let paymentMint: PublicKey;
+    let treasuryOwner: PublicKey;
+
+    const paymentAccount = (owner: PublicKey) =>
+      getAssociatedTokenAddress(paymentMint, owner, true);
+
+    const setPaymentMint = (accepted: boolean) =>
+      program.methods
+        .setPaymentMint(paymentMint, accepted)
+        .accountsStrict({
+          authority: provider.wallet.publicKey,
+          config: configPda,
+          adminSet: null,
+          proposal: null,
+          proposer: null,
+        })
+        .rpc({ commitment: "confirmed" });
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(buyer.publicKey);
+      await airdrop(mintAuthority.publicKey);
+
+      paymentMint = await createMint(
+        provider.connection,
+        mintAuthority,
+        mintAuthority.publicKey,
+        null,
+        6
+      );
+      treasuryOwner = (await program.account.marketplaceConfig.fetch(configPda)).treasury;
+      for (const owner of [
+        seller.publicKey,
+        buyer.publicKey,
+        COLLECTION_AUTHORITY,
+        treasuryPda,
+        treasuryOwner,
+      ]) {
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          mintAuthority,
+          paymentMint,
+          owner,
+          true
+        );
+      }
+      await mintTo(
+        provider.connection,
+        mintAuthority,
+        paymentMint,
+        await paymentAccount(buyer.publicKey),
+        mintAuthority,
+        PRICE * 2
+      );
+    });
+
+    after(async () => {
+      await setPaymentMint(false);
+    });
+
+    it("Rejects listings priced in a mint the marketplace doesn't accept", async () => {
+      const mint = await mintCard(seller);
+      await expectError(listCard(seller, mint, PRICE, { paymentMint }), "PaymentMintNotAccepted");
+    });
+
+    it("Pays the seller, creator and treasury associated token accounts", async () => {
+      await setPaymentMint(true);
+      const config = await program.account.marketplaceConfig.fetch(configPda);
+      assert.ok(config.paymentMints.some((accepted) => accepted.equals(paymentMint)));
+
+      const mint = await mintCard(seller);
+      await listCard(seller, mint, PRICE, { paymentMint });
+
+      const buyerPayment = await paymentAccount(buyer.publicKey);
+      const sellerPayment = await paymentAccount(seller.publicKey);
+      const creatorPayment = await paymentAccount(COLLECTION_AUTHORITY);
+      const treasuryPayment = await paymentAccount(treasuryPda);
+      const balancesBefore = await Promise.all(
+        [buyerPayment, sellerPayment, creatorPayment, treasuryPayment].map(tokenBalance)
+      );
+
+      await program.methods
+        .purchaseNft()
+        .accountsStrict({
+          buyer: buyer.publicKey,
+          seller: seller.publicKey,
+          config: configPda,
+          treasury: treasuryPda,
+          listing: listingPda(mint),
+          mint,
+          metadata: metadataPda(mint),
+          sellerToken: await getAssociatedTokenAddress(mint, seller.publicKey),
+          vault: null,
+          buyerToken: await getAssociatedTokenAddress(mint, buyer.publicKey),
+          buyerPaymentAccount: buyerPayment,
+          sellerPaymentAccount: sellerPayment,
+          treasuryPaymentAccount: treasuryPayment,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts([{ pubkey: creatorPayment, isSigner: false, isWritable: true }])
+        .signers([buyer])
+        .rpc({ commitment: "confirmed" });
+
+      const balancesAfter = await Promise.all(
+        [buyerPayment, sellerPayment, creatorPayment, treasuryPayment].map(tokenBalance)
+      );
+      assert.equal(balancesBefore[0] - balancesAfter[0], PRICE);
+      assert.equal(balancesAfter[1] - balancesBefore[1], PRICE - ROYALTY - PLATFORM_FEE);
+      assert.equal(balancesAfter[2] - balancesBefore[2], ROYALTY);
+      assert.equal(balancesAfter[3] - balancesBefore[3], PLATFORM_FEE);
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, buyer.publicKey)), 1);
+    });
+
+    it("Withdraws token fees from the treasury to the configured treasury", async () => {
+      const treasuryPayment = await paymentAccount(treasuryPda);
+      const destination = await paymentAccount(treasuryOwner);
+      const fees = await tokenBalance(treasuryPayment);
+      const destinationBefore = await tokenBalance(destination);
+
+      await program.methods
+        .withdrawTreasuryTokens(new anchor.BN(fees))
+        .accountsStrict({
+          authority: provider.wallet.publicKey,
+          config: configPda,
+          adminSet: null,
+          proposal: null,
+          proposer: null,
+          treasury: treasuryPda,
+          paymentMint,
+          treasuryTokenAccount: treasuryPayment,
+          destination,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      assert.equal(await tokenBalance(treasuryPayment), 0);
+      assert.equal((await tokenBalance(destination)) - destinationBefore, fees);
+    });
+  });
+
+  describe("pausing", () => {
+    const seller = Keypair.generate();
+    const buyer = Keypair.generate();
+
+    const setPaused = (scope: any, paused: boolean) =>
+      program.methods
+        .setPaused(scope, paused)
+        .accountsStrict({
+          authority: provider.wallet.publicKey,
+          config: configPda,
+          adminSet: null,
+          proposal: null,
+          proposer: null,
+        })
+        .rpc({ commitment: "confirmed" });
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(buyer.publicKey);
+    });
+
+    after(async () => {
+      await setPaused({ all: {} }, false);
+      await setPaused({ mint: {} }, false);
+    });
+
+    it("Rejects listing, purchases and mints while paused", async () => {
+      const unlisted = await mintCard(seller);
+      const listed = await mintCard(seller);
+      await listCard(seller, listed, 1_000_000_000);
+
+      await setPaused({ all: {} }, true);
+      await expectError(listCard(seller, unlisted, 1_000_000_000), "ProgramPaused");
+      await expectError(purchaseCard(buyer, seller.publicKey, listed), "ProgramPaused");
+      await expectError(mintCard(seller), "ProgramPaused");
+
+      await setPaused({ all: {} }, false);
+      await purchaseCard(buyer, seller.publicKey, listed);
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(listed, buyer.publicKey)), 1);
+    });
+
+    it("Only pauses the scope that was paused", async () => {
+      const mint = await mintCard(seller);
+
+      await setPaused({ mint: {} }, true);
+      assert.isTrue((await program.account.marketplaceConfig.fetch(configPda)).mintPaused);
+      await expectError(mintCard(seller), "ProgramPaused");
+      await listCard(seller, mint, 1_000_000_000);
+      await cancelCard(seller, mint);
+
+      await setPaused({ mint: {} }, false);
+    });
+  });
+
+  describe("authority handoff", () => {
+    const newAuthority = Keypair.generate();
+    const stranger = Keypair.generate();
+    let card: PublicKey;
+
+    const acceptAuthority = (oldAuthority: PublicKey, incoming: PublicKey, signers: Keypair[]) =>
+      program.methods
+        .acceptAuthority()
+        .accountsStrict({
+          authority: oldAuthority,
+          newAuthority: incoming,
+          config: configPda,
+          adminSet: null,
+          proposal: null,
+          proposer: null,
+          metadataProgram: TOKEN_METADATA_PROGRAM_ID,
+        })
+        .remainingAccounts([
+          { pubkey: registryPda(), isSigner: false, isWritable: true },
+          { pubkey: metadataPda(collectionMint.publicKey), isSigner: false, isWritable: true },
+          { pubkey: metadataPda(card), isSigner: false, isWritable: true },
+        ])
+        .signers(signers)
+        .rpc({ commitment: "confirmed" });
+
+    const updateAuthority = async (mint: PublicKey) =>
+      (await Metadata.fromAccountAddress(provider.connection, metadataPda(mint), "confirmed"))
+        .updateAuthority;
+
+    before(async () => {
+      await airdrop(newAuthority.publicKey);
+      card = await mintCard(newAuthority);
+    });
+
+    it("Rejects a signer other than the proposed authority", async () => {
+      await applyChange({ authority: { 0: newAuthority.publicKey } });
+      await expectError(
+        acceptAuthority(provider.wallet.publicKey, stranger.publicKey, [stranger]),
+        "InvalidNewAuthority"
+      );
+      assert.ok(
+        (await program.account.marketplaceConfig.fetch(configPda)).authority.equals(
+          provider.wallet.publicKey
+        )
+      );
+    });
+
+    it("Moves the config, registries and metadata to the new authority and back", async () => {
+      await acceptAuthority(provider.wallet.publicKey, newAuthority.publicKey, [newAuthority]);
+
+      const config = await program.account.marketplaceConfig.fetch(configPda);
+      assert.ok(config.authority.equals(newAuthority.publicKey));
+      assert.isNull(config.pendingAuthority);
+      assert.ok(
+        (await program.account.collectionRegistry.fetch(registryPda())).authority.equals(
+          newAuthority.publicKey
+        )
+      );
+      assert.ok((await updateAuthority(collectionMint.publicKey)).equals(newAuthority.publicKey));
+      assert.ok((await updateAuthority(card)).equals(newAuthority.publicKey));
+
+      // Hand everything back so the remaining tests keep the provider wallet
+      await applyChange({ authority: { 0: provider.wallet.publicKey } }, newAuthority);
+      await acceptAuthority(newAuthority.publicKey, provider.wallet.publicKey, [newAuthority]);
+      assert.ok(
+        (await program.account.marketplaceConfig.fetch(configPda)).authority.equals(
+          provider.wallet.publicKey
+        )
+      );
+      assert.ok((await updateAuthority(card)).equals(provider.wallet.publicKey));
+    });
+  });
+
+  describe("pruning stale listings", () => {
+    const seller = Keypair.generate();
+    const pruner = Keypair.generate();
+    const BOUNTY = 5_000_000;
+
+    const pruneListing = (mint: PublicKey) =>
+      program.methods
+        .pruneListing()
+        .accountsStrict({
+          caller: pruner.publicKey,
+          listing: listingPda(mint),
+          sellerToken: getAssociatedTokenAddressSync(mint, seller.publicKey),
+        })
+        .signers([pruner])
+        .rpc({ commitment: "confirmed" });
+
+    before(async () => {
+      await airdrop(seller.publicKey);
+      await airdrop(pruner.publicKey, 1);
+      await applyChange({ pruneBounty: { 0: new anchor.BN(BOUNTY) } });
+    });
+
+    after(async () => {
+      await applyChange({ pruneBounty: { 0: new anchor.BN(0) } });
+    });
+
+    it("Pays the seller's deposit to whoever prunes a stale listing", async () => {
+      const mint = await mintCard(seller);
+      await listCard(seller, mint, 1_000_000_000);
+      assert.equal(
+        (await program.account.listingData.fetch(listingPda(mint))).bountyDeposit.toNumber(),
+        BOUNTY
+      );
+
+      // Revoking the delegate leaves the listing unfillable
+      await revoke(provider.connection, seller, getAssociatedTokenAddressSync(mint, seller.publicKey), seller);
+
+      const prunerBefore = await balance(pruner.publicKey);
+      await pruneListing(mint);
+
+      // The provider wallet pays the fee, so the pruner gets the whole deposit
+      assert.equal((await balance(pruner.publicKey)) - prunerBefore, BOUNTY);
+      const listing = await program.account.listingData.fetch(listingPda(mint), "confirmed");
+      assert.deepEqual(listing.status, { notActive: {} });
+      assert.equal(listing.bountyDeposit.toNumber(), 0);
+      assert.deepEqual(listing.history[listing.history.length - 1].action, { invalidated: {} });
+    });
+
+    it("Rejects pruning a listing the seller still backs", async () => {
+      const mint = await mintCard(seller);
+      await listCard(seller, mint, 1_000_000_000);
+
+      await expectError(pruneListing(mint), "ListingNotStale");
+
+      const sellerBefore = await balance(seller.publicKey);
+      await cancelCard(seller, mint);
+      const listing = await program.account.listingData.fetch(listingPda(mint), "confirmed");
+      assert.equal(listing.bountyDeposit.toNumber(), 0);
+      assert.equal((await balance(seller.publicKey)) - sellerBefore, BOUNTY);
+    });
+  });
+
+  describe("mint phases", () => {
+    const allowlisted = Keypair.generate();
+    const outsider = Keypair.generate();
+    const ALLOWANCE = 2;
+    const ALLOWLIST_PRICE = 50_000_000;
+    const PUBLIC_PRICE = 80_000_000;
+
+    // Leaves and sorted-pair nodes match the program's keccak merkle tree
+    const allowlistLeaf = (wallet: PublicKey, allowance: number) => {
+      const allowanceBytes = Buffer.alloc(4);
+      allowanceBytes.writeUInt32LE(allowance);
+      return Buffer.from(keccak_256(Buffer.concat([wallet.toBuffer(), allowanceBytes])));
+    };
+    const hashPair = (a: Buffer, b: Buffer) =>
+      Buffer.from(keccak_256(Buffer.concat(Buffer.compare(a, b) <= 0 ? [a, b] : [b, a])));
+
+    const otherLeaf = allowlistLeaf(Keypair.generate().publicKey, 1);
+    const root = hashPair(allowlistLeaf(allowlisted.publicKey, ALLOWANCE), otherLeaf);
+
+    const updateCollection = () => ({
+      authority: provider.wallet.publicKey,
+      collectionRegistry: registryPda(),
+    });
+
+    const setPhases = (phases: any[]) =>
+      program.methods
+        .setMintPhases(phases)
+        .accountsStrict(updateCollection())
+        .rpc({ commitment: "confirmed" });
+
+    const allowlistClaimPda = (wallet: PublicKey) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("allowlist"), collectionMint.publicKey.toBuffer(), wallet.toBuffer()],
+        program.programId
+      )[0];
+
+    const mintAllowlisted = async (owner: Keypair, proof: Buffer[]) => {
+      const mint = Keypair.generate();
+      await program.methods
+        .mintNftAllowlisted(
+          nftName,
+          nftSymbol,
+          nftUri,
+          cardStats(mint.publicKey, {}),
+          ALLOWANCE,
+          proof.map((node) => Array.from(node))
+        )
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 300000 })])
+        .accountsStrict({
+          mintNft: await mintAccounts(owner.publicKey, mint.publicKey, true),
+          allowlistClaim: allowlistClaimPda(owner.publicKey),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([mint, owner])
+        .rpc({ commitment: "confirmed" });
+      return mint.publicKey;
+    };
+
+    before(async () => {
+      await airdrop(allowlisted.publicKey);
+      await airdrop(outsider.publicKey);
+      await program.methods
+        .setAllowlistRoot(Array.from(root))
+        .accountsStrict(updateCollection())
+        .rpc({ commitment: "confirmed" });
+    });
+
+    after(async () => {
+      await setPhases([]);
+      await program.methods.setAllowlistRoot(null).accountsStrict(updateCollection()).rpc();
+    });
+
+    it("Rejects public mints outside every phase", async () => {
+      const now = await chainTime();
+      await setPhases([
+        {
+          startAt: new anchor.BN(now + 3_600),
+          endAt: new anchor.BN(now + 7_200),
+          price: new anchor.BN(PUBLIC_PRICE),
+          eligibility: { public: {} },
+          maxPerWallet: 0,
+        },
+      ]);
+
+      await expectError(mintCard(outsider, {}, { publicMint: true }), "MintPhaseNotActive");
+    });
+
+    it("Moves from the allowlist phase to the public phase", async () => {
+      const now = await chainTime();
+      const switchAt = now + 15;
+      await setPhases([
+        {
+          startAt: new anchor.BN(now - 60),
+          endAt: new anchor.BN(switchAt),
+          price: new anchor.BN(ALLOWLIST_PRICE),
+          eligibility: { allowlist: {} },
+          maxPerWallet: 0,
+        },
+        {
+          startAt: new anchor.BN(switchAt),
+          endAt: new anchor.BN(switchAt + 3_600),
+          price: new anchor.BN(PUBLIC_PRICE),
+          eligibility: { public: {} },
+          maxPerWallet: 1,
+        },
+      ]);
+
+      // Only allowlisted wallets can mint during the allowlist phase
+      await expectError(mintCard(outsider, {}, { publicMint: true }), "MintPhaseNotActive");
+      await expectError(mintAllowlisted(outsider, [otherLeaf]), "InvalidAllowlistProof");
+
+      let registryBefore = await balance(registryPda());
+      await mintAllowlisted(allowlisted, [otherLeaf]);
+      assert.equal((await balance(registryPda())) - registryBefore, ALLOWLIST_PRICE);
+
+      await waitUntil(switchAt);
+
+      // The public phase charges its own price and per-wallet limit
+      await expectError(mintAllowlisted(allowlisted, [otherLeaf]), "MintPhaseNotActive");
+      registryBefore = await balance(registryPda());
+      await mintCard(outsider, {}, { publicMint: true });
+      assert.equal((await balance(registryPda())) - registryBefore, PUBLIC_PRICE);
+      await expectError(mintCard(outsider, {}, { publicMint: true }), "MintLimitExceeded");
+    });
+  });
+
+  describe("public mints", () => {
+    const minter = Keypair.generate();
+    const MINT_PRICE = 100_000_000;
+
+    const updateCollection = () => ({
+      authority: provider.wallet.publicKey,
+      collectionRegistry: registryPda(),
+    });
+
+    before(async () => {
+      await airdrop(minter.publicKey);
+    });
+
+    after(async () => {
+      await program.methods.setMintPrice(new anchor.BN(0)).accountsStrict(updateCollection()).rpc();
+      await program.methods.setMintLimit(0).accountsStrict(updateCollection()).rpc();
+    });
+
+    it("Rejects public mints while the collection has no price", async () => {
+      await expectError(mintCard(minter, {}, { publicMint: true }), "PublicMintDisabled");
+    });
+
+    it("Charges the mint price to the collection and counts the wallet's mints", async () => {
+      await program.methods
+        .setMintPrice(new anchor.BN(MINT_PRICE))
+        .accountsStrict(updateCollection())
+        .rpc({ commitment: "confirmed" });
+
+      const registryBefore = await balance(registryPda());
+      const mint = await mintCard(minter, {}, { publicMint: true });
+
+      assert.equal((await balance(registryPda())) - registryBefore, MINT_PRICE);
+      assert.equal(await tokenBalance(await getAssociatedTokenAddress(mint, minter.publicKey)), 1);
+      assert.equal((await program.account.mintCounter.fetch(mintCounterPda(minter.publicKey))).minted, 1);
+    });
+
+    it("Stops a wallet at the collection's mint limit", async () => {
+      await program.methods.setMintLimit(2).accountsStrict(updateCollection()).rpc({ commitment: "confirmed" });
+
+      await mintCard(minter, {}, { publicMint: true });
+      await expectError(mintCard(minter, {}, { publicMint: true }), "MintLimitExceeded");
+      assert.equal((await program.account.mintCounter.fetch(mintCounterPda(minter.publicKey))).minted, 2);
+    });
+
+    // Caps can't be raised again, so this runs after every other minting test
+    it("Stops every mint once the supply cap is reached", async () => {
+      const { minted } = await program.account.collectionRegistry.fetch(registryPda());
+      const rarityCaps = [0, 0, 0, 0, 0, 0];
+      await program.methods
+        .setSupplyCaps(minted.addn(1), rarityCaps)
+        .accountsStrict(updateCollection())
+        .rpc({ commitment: "confirmed" });
+
+      await mintCard(minter);
+      await expectError(mintCard(minter), "MaxSupplyReached");
+      await expectError(
+        program.methods
+          .setSupplyCaps(minted.addn(5), rarityCaps)
+          .accountsStrict(updateCollection())
+          .rpc(),
+        "InvalidSupplyCap"
+      );
+    });
+  });
+
+  // Creating an admin set can't be undone, so this runs last
+  describe("admin set", () => {
+    const second = Keypair.generate();
+    const third = Keypair.generate();
+    const wallet = provider.wallet.publicKey;
+
+    const [adminSetPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("admin_set")],
+      program.programId
+    );
+    const proposalPda = (id: anchor.BN) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("proposal"), id.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+
+    // Proposes `action` as the provider wallet, which approves it too
+    const propose = async (action: any) => {
+      const { nextProposalId } = await program.account.adminSet.fetch(adminSetPda);
+      await program.methods
+        .createProposal(action)
+        .accountsStrict({
+          proposer: wallet,
+          adminSet: adminSetPda,
+          proposal: proposalPda(nextProposalId),
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc({ commitment: "confirmed" });
+      return proposalPda(nextProposalId);
+    };
+
+    const approve = (proposal: PublicKey, approver: Keypair) =>
+      program.methods
+        .approveProposal()
+        .accountsStrict({ approver: approver.publicKey, adminSet: adminSetPda, proposal })
+        .signers([approver])
+        .rpc({ commitment: "confirmed" });
+
+    const setPaused = (scope: any, paused: boolean, proposal: PublicKey | null) =>
+      program.methods
+        .setPaused(scope, paused)
+        .accountsStrict({
+          authority: wallet,
+          config: configPda,
+          adminSet: adminSetPda,
+          proposal,
+          proposer: proposal ? wallet : null,
+        })
+        .rpc({ commitment: "confirmed" });
+
+    it("Creates a 2 of 3 admin set and stops single-key actions", async () => {
+      await program.methods
+        .createAdminSet([wallet, second.publicKey, third.publicKey], 2)
+        .accountsStrict({
+          authority: wallet,
+          config: configPda,
+          adminSet: adminSetPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      assert.isTrue((await program.account.marketplaceConfig.fetch(configPda)).multisig);
+      const adminSet = await program.account.adminSet.fetch(adminSetPda);
+      assert.equal(adminSet.threshold, 2);
+      assert.equal(adminSet.signers.length, 3);
+
+      await expectError(setPaused({ purchase: {} }, true, null), "ProposalRequired");
+    });
+
+    it("Runs a proposal once it reaches the threshold and closes it", async () => {
+      const proposal = await propose({ setPaused: { scope: { purchase: {} }, paused: true } });
+
+      await expectError(setPaused({ purchase: {} }, true, proposal), "ProposalNotApproved");
+      await expectError(
+        approve(proposal, (provider.wallet as anchor.Wallet).payer),
+        "AlreadyApproved"
+      );
+
+      await approve(proposal, second);
+      await expectError(setPaused({ purchase: {} }, false, proposal), "ProposalActionMismatch");
+      await setPaused({ purchase: {} }, true, proposal);
+
+      assert.isTrue((await program.account.marketplaceConfig.fetch(configPda)).purchasePaused);
+      assert.isNull(await provider.connection.getAccountInfo(proposal, "confirmed"));
+
+      const unpause = await propose({ setPaused: { scope: { purchase: {} }, paused: false } });
+      await approve(unpause, third);
+      await setPaused({ purchase: {} }, false, unpause);
+      assert.isFalse((await program.account.marketplaceConfig.fetch(configPda)).purchasePaused);
+    });
+
+    it("Updates the admin set through an approved proposal", async () => {
+      const signers = [wallet, second.publicKey];
+      const proposal = await propose({ updateAdminSet: { signers, threshold: 1 } });
+      await approve(proposal, third);
+
+      await program.methods
+        .updateAdminSet(signers, 1)
+        .accountsStrict({
+          authority: wallet,
+          config: configPda,
+          adminSet: adminSetPda,
+          proposal,
+          proposer: wallet,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const adminSet = await program.account.adminSet.fetch(adminSetPda);
+      assert.equal(adminSet.threshold, 1);
+      assert.deepEqual(
+        adminSet.signers.map((signer) => signer.toBase58()),
+        signers.map((signer) => signer.toBase58())
+      );
+      assert.isNull(await provider.connection.getAccountInfo(proposal, "confirmed"));
+    });
+  });
+});